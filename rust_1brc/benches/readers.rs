@@ -0,0 +1,89 @@
+//! The std solver end to end with each of its `--reader`s.
+//!
+//! Run with `cargo bench --bench readers`. Every iteration runs the release `1brc` binary on the
+//! same file, so the file is in the page cache after the first one and the comparison is of how
+//! each reader gets pages from the cache into the parser, not of the disk. By default a file of
+//! [`ROWS`] rows is generated under the target directory; set `BENCH_MEASUREMENTS` to use an
+//! existing measurements file instead.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+#[allow(unused)]
+#[path = "../src/bin/generate/cities.rs"]
+mod cities;
+
+const ROWS: usize = 20_000_000;
+
+/// A small xorshift generator, so the file is the same on every run
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+/// The measurements file to read, generating it first if there isn't one yet.
+fn measurements() -> PathBuf {
+    if let Some(path) = std::env::var_os("BENCH_MEASUREMENTS") {
+        return path.into();
+    }
+
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("readers-{ROWS}.txt"));
+    if path.exists() {
+        return path;
+    }
+
+    let partial = path.with_extension("partial");
+    let mut out = BufWriter::new(File::create(&partial).expect("creating the measurements file"));
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    for _ in 0..ROWS {
+        let (name, mean) = cities::DATA[rng.below(cities::DATA.len())];
+        let tenths = ((mean * 10.0) as i64 + rng.below(201) as i64 - 100).clamp(-999, 999);
+        let sign = if tenths < 0 { "-" } else { "" };
+        let (abs, tenths) = (tenths.abs() / 10, tenths.abs() % 10);
+        writeln!(out, "{name};{sign}{abs}.{tenths}").expect("writing the measurements file");
+    }
+    out.flush().expect("writing the measurements file");
+    std::fs::rename(&partial, &path).expect("moving the measurements file into place");
+    path
+}
+
+fn readers(c: &mut Criterion) {
+    let input = measurements();
+
+    let mut group = c.benchmark_group("readers");
+    group.sample_size(10);
+    for reader in ["pread", "mmap", "io-uring"] {
+        group.bench_with_input(BenchmarkId::from_parameter(reader), reader, |b, reader| {
+            b.iter(|| {
+                let status = Command::new(env!("CARGO_BIN_EXE_1brc"))
+                    .args(["--solver", "std", "--reader", reader, "--input"])
+                    .arg(&input)
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status()
+                    .expect("running 1brc");
+                assert!(status.success(), "1brc --reader {reader} failed");
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, readers);
+criterion_main!(benches);
@@ -0,0 +1,187 @@
+//! Aggregating a stream of station names into a table, for each of the hash maps the solvers use.
+//!
+//! Run with `cargo bench --bench station_table`. Every iteration starts from an empty table, like
+//! a worker thread does, and folds the same million lookups into it. The real 413 cities are
+//! compared, as well as 10,000 made-up names of 1 to 100 bytes for the 10K variant.
+
+use std::{collections::HashMap, hash::BuildHasher};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+// the bench only needs some of each module
+#[allow(unused)]
+#[path = "../src/bin/generate/cities.rs"]
+mod cities;
+#[allow(unused)]
+#[path = "../src/table.rs"]
+mod table;
+
+use table::StationTable;
+
+const LOOKUPS: usize = 1_000_000;
+
+type PtrHash = ptr_hash::DefaultPtrHash<ptr_hash::hash::FxHash, u64>;
+
+#[derive(Debug, Clone, Copy)]
+struct Aggregate {
+    min: i32,
+    max: i32,
+    sum: i64,
+    count: u64,
+}
+
+impl Default for Aggregate {
+    fn default() -> Self {
+        Self {
+            min: i32::MAX,
+            max: i32::MIN,
+            sum: 0,
+            count: 0,
+        }
+    }
+}
+
+impl Aggregate {
+    fn add(&mut self, value: i32) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum += value as i64;
+        self.count += 1;
+    }
+}
+
+/// A small xorshift generator, so the names and lookups are the same on every run
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+fn synthetic_names(count: usize, rng: &mut Rng) -> Vec<Vec<u8>> {
+    let mut names = std::collections::HashSet::new();
+    while names.len() < count {
+        let len = 1 + rng.below(100);
+        let name = (0..len)
+            .map(|_| b"abcdefghijklmnopqrstuvwxyz -'"[rng.below(29)])
+            .collect::<Vec<u8>>();
+        names.insert(name);
+    }
+    names.into_iter().collect()
+}
+
+/// Which station each lookup is for, and its temperature in tenths
+fn lookups(stations: usize, rng: &mut Rng) -> Vec<(usize, i32)> {
+    (0..LOOKUPS)
+        .map(|_| (rng.below(stations), rng.below(1999) as i32 - 999))
+        .collect()
+}
+
+/// Aggregate `lookups` into a map that `entry` looks names up in.
+fn bench_map<M>(
+    names: &[Vec<u8>],
+    lookups: &[(usize, i32)],
+    mut map: M,
+    mut entry: impl for<'m> FnMut(&'m mut M, &[u8]) -> &'m mut Aggregate,
+) -> M {
+    for &(station, value) in lookups {
+        entry(&mut map, &names[station]).add(value);
+    }
+    map
+}
+
+fn entry<'a, H: BuildHasher>(
+    map: &'a mut HashMap<Vec<u8>, Aggregate, H>,
+    name: &[u8],
+) -> &'a mut Aggregate {
+    // look up before inserting, as the solvers do, so the key is only copied once
+    if !map.contains_key(name) {
+        map.insert(name.to_vec(), Aggregate::default());
+    }
+    map.get_mut(name).expect("just inserted")
+}
+
+fn build_ptr_hash(names: &[Vec<u8>]) -> PtrHash {
+    let keys = names
+        .iter()
+        .map(|name| fxhash::hash64(name))
+        .collect::<Vec<_>>();
+    let params = ptr_hash::PtrHashParams {
+        alpha: 0.9,
+        c: 1.5,
+        slots_per_part: 2 * keys.len(),
+        ..ptr_hash::PtrHashParams::default()
+    };
+    PtrHash::new(&keys, params)
+}
+
+fn station_tables(c: &mut Criterion) {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    let cities = cities::DATA
+        .iter()
+        .map(|(name, _)| name.as_bytes().to_vec())
+        .collect::<Vec<_>>();
+    let sets = [
+        ("cities", cities),
+        ("synthetic-10k", synthetic_names(10_000, &mut rng)),
+    ];
+
+    let mut group = c.benchmark_group("station_table");
+    for (set, names) in &sets {
+        let lookups = lookups(names.len(), &mut rng);
+        let lookups = lookups.as_slice();
+
+        group.bench_with_input(BenchmarkId::new("StationTable", set), names, |b, names| {
+            b.iter(|| {
+                let table = StationTable::<Aggregate>::new();
+                black_box(bench_map(names, lookups, table, |table, name| {
+                    table.get_or_default(name)
+                }))
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("std HashMap", set), names, |b, names| {
+            b.iter(|| black_box(bench_map(names, lookups, HashMap::new(), entry)))
+        });
+        group.bench_with_input(BenchmarkId::new("AHashMap", set), names, |b, names| {
+            b.iter(|| {
+                let map = HashMap::with_hasher(ahash::RandomState::new());
+                black_box(bench_map(names, lookups, map, entry))
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("fxhash", set), names, |b, names| {
+            b.iter(|| {
+                let map = fxhash::FxHashMap::default();
+                black_box(bench_map(names, lookups, map, entry))
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("rustc-hash", set), names, |b, names| {
+            b.iter(|| {
+                let map = rustc_hash::FxHashMap::default();
+                black_box(bench_map(names, lookups, map, entry))
+            })
+        });
+
+        // a perfect hash only works for the names it was built from, so it is built up front
+        let phf = build_ptr_hash(names);
+        group.bench_with_input(BenchmarkId::new("PtrHash", set), names, |b, names| {
+            b.iter(|| {
+                let slots = vec![Aggregate::default(); 2 * names.len()];
+                black_box(bench_map(names, lookups, slots, |slots, name| {
+                    &mut slots[phf.index_single_part(&fxhash::hash64(name))]
+                }))
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, station_tables);
+criterion_main!(benches);
@@ -0,0 +1,55 @@
+//! Datasets aimed at the known weak spots of the solvers, picked with `--adversarial`.
+
+use anyhow::Result;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{cities::DATA, names, tenths, Span};
+
+/// Most rows a [`Scenario::Tiny`] file gets. The longest real city makes 33-byte lines, so this
+/// stays well under 100 KB.
+pub const TINY_ROWS: usize = 2_000;
+
+/// Temperatures at the edges of what the format allows, in tenths
+pub const EXTREMES: [i16; 3] = [-tenths::LIMIT, tenths::LIMIT, tenths::NEGATIVE_ZERO];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Scenario {
+    /// Names sharing their first and last 8 bytes, which all get the same ragnar `to_key`
+    SharedAffixes,
+    /// Names of exactly 100 bytes, the longest the rules allow
+    LongNames,
+    /// The real cities, with a third of the temperatures at -99.9, 99.9 or -0.0
+    Extremes,
+    /// Every row from the same station
+    SingleStation,
+    /// Names of every length from 1 to 100 bytes, so that line breaks land at every offset
+    /// around chunk and SIMD boundaries
+    Boundaries,
+    /// At most 2,000 rows, less than the 100 KB ragnar samples to find the station names
+    Tiny,
+}
+
+impl Scenario {
+    /// Station names for this scenario, with the mean temperature of each.
+    pub fn stations(self, rng: &mut impl Rng) -> Result<Vec<(String, f64)>> {
+        let names = match self {
+            Scenario::Extremes | Scenario::Tiny => {
+                return Ok(DATA
+                    .iter()
+                    .map(|&(city, mean)| (city.to_owned(), mean))
+                    .collect())
+            }
+            Scenario::SingleStation => {
+                let &(city, mean) = DATA.choose(rng).expect("DATA is not empty");
+                return Ok(vec![(city.to_owned(), mean)]);
+            }
+            Scenario::SharedAffixes => names::synthesize(rng, 100, Span { low: 1, high: 20 }, 0.0)?
+                .into_iter()
+                .map(|middle| format!("Port of {middle} Harbour"))
+                .collect(),
+            Scenario::LongNames => names::synthesize(rng, 100, Span::exactly(100), 0.2)?,
+            Scenario::Boundaries => (1..=100).map(|len| names::name(rng, len, 0.0)).collect(),
+        };
+        Ok(crate::with_means(rng, names))
+    }
+}
@@ -0,0 +1,55 @@
+//! The binary measurements format, written when `--output` ends in `.bin` (or `.bin.gz` or
+//! `.bin.zst`).
+//!
+//! A file is a header naming every station, followed by one fixed-size record per row:
+//!
+//! | bytes    | contents                                          |
+//! |----------|---------------------------------------------------|
+//! | 4        | the magic bytes `1BRC`                            |
+//! | 1        | the format version, [`VERSION`]                   |
+//! | 4        | the number of stations, little-endian `u32`       |
+//! | 1 + len  | for each station, its name's length and its UTF-8 |
+//! | 4 per row| the station's index and the temperature in tenths, both little-endian (`u16`, `i16`) |
+//!
+//! Rows are all [`ROW_BYTES`] long, so a reader can split them between threads at any multiple of
+//! that without scanning for line ends. Every shard starts with the full header, so each can be read
+//! on its own. `-0.0` has no integer of its own and is written as 0.
+
+use anyhow::{bail, Result};
+
+use crate::tenths;
+
+pub const MAGIC: &[u8; 4] = b"1BRC";
+pub const VERSION: u8 = 1;
+/// The size of one row: a `u16` station index and an `i16` temperature
+pub const ROW_BYTES: usize = 4;
+
+/// The header for a file of rows from `names`.
+pub fn header(names: &[&str]) -> Result<Vec<u8>> {
+    if names.len() > u16::MAX as usize + 1 {
+        bail!(
+            "the binary format holds at most {} stations, not {}",
+            u16::MAX as usize + 1,
+            names.len()
+        );
+    }
+
+    let mut header = Vec::with_capacity(9 + names.iter().map(|name| 1 + name.len()).sum::<usize>());
+    header.extend_from_slice(MAGIC);
+    header.push(VERSION);
+    header.extend_from_slice(&(names.len() as u32).to_le_bytes());
+    for name in names {
+        let Ok(len) = u8::try_from(name.len()) else {
+            bail!("the binary format holds names of at most 255 bytes, not {name:?}");
+        };
+        header.push(len);
+        header.extend_from_slice(name.as_bytes());
+    }
+    Ok(header)
+}
+
+/// Append a row for the station at index `station` of the header, at `temp` tenths.
+pub fn push(out: &mut Vec<u8>, station: usize, temp: i16) {
+    out.extend_from_slice(&(station as u16).to_le_bytes());
+    out.extend_from_slice(&(tenths::value(temp) as i16).to_le_bytes());
+}
@@ -0,0 +1,91 @@
+//! Station catalogues loaded with `--catalogue`, to model a fleet of sensors other than the
+//! built-in cities.
+//!
+//! Each line is `name;mean[;stddev[;weight]]`. A missing standard deviation is drawn from
+//! `--std-dev` like for the built-in cities, and a missing weight is 1. Empty lines and lines
+//! starting with `#` are skipped.
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub name: String,
+    pub mean: f64,
+    pub std_dev: Option<f64>,
+    /// How often the station comes up relative to the others
+    pub weight: f64,
+}
+
+impl Entry {
+    pub fn new(name: String, mean: f64) -> Self {
+        Self {
+            name,
+            mean,
+            std_dev: None,
+            weight: 1.0,
+        }
+    }
+}
+
+fn parse_line(line: &str) -> Result<Entry> {
+    let mut fields = line.split(';');
+    let name = fields.next().unwrap_or_default();
+    if name.is_empty() || name.len() > 100 {
+        bail!("station names must be 1 to 100 bytes long");
+    }
+    let mean = fields
+        .next()
+        .context("missing mean")?
+        .trim()
+        .parse()
+        .context("parsing mean")?;
+    let std_dev = fields
+        .next()
+        .map(|std_dev| std_dev.trim().parse().context("parsing standard deviation"))
+        .transpose()?;
+    let weight = fields
+        .next()
+        .map(|weight| weight.trim().parse().context("parsing weight"))
+        .transpose()?
+        .unwrap_or(1.0);
+    if fields.next().is_some() {
+        bail!("expected at most 4 fields");
+    }
+    if !(0.0..f64::INFINITY).contains(&weight) {
+        bail!("weight must be finite and not negative");
+    }
+
+    Ok(Entry {
+        name: name.to_owned(),
+        mean,
+        std_dev,
+        weight,
+    })
+}
+
+pub fn load(path: &Path) -> Result<Vec<Entry>> {
+    let catalogue = std::fs::read_to_string(path)
+        .with_context(|| format!("reading catalogue {}", path.display()))?;
+
+    let mut entries = Vec::new();
+    let mut seen = rustc_hash::FxHashSet::default();
+    for (idx, line) in catalogue.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let entry = parse_line(line)
+            .with_context(|| format!("{}:{}: {line:?}", path.display(), idx + 1))?;
+        if !seen.insert(entry.name.clone()) {
+            bail!(
+                "{}:{}: duplicate station {:?}",
+                path.display(),
+                idx + 1,
+                entry.name
+            );
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
@@ -1,44 +1,6 @@
-//! Generate a file with an arbitrary number of rows.
-//!
-//! See reference implementation: https://github.com/gunnarmorling/1brc/blob/main/src/main/java/dev/morling/onebrc/CreateMeasurements.java
+//! The real cities and their mean temperatures, from the reference implementation.
 
-use anyhow::{anyhow, Context, Result};
-use rand::seq::SliceRandom;
-use rand_distr::{Distribution, Normal};
-use std::io::Write;
-
-fn main() -> Result<()> {
-    let n = std::env::args()
-        .nth(1)
-        .ok_or_else(|| anyhow!("requires at least one argument"))?
-        .parse::<usize>()
-        .context("must be able to parse as usize")?;
-
-    let mut rng = rand::thread_rng();
-
-    let file =
-        std::fs::File::create("../measurements.txt").context("opening output file for write")?;
-    let mut buf = std::io::BufWriter::new(file);
-
-    let std_devs = Normal::new(10.0, 2.5).context("std_devs is valid normal distribution")?;
-    let mut data = Vec::with_capacity(DATA.len());
-    for (city, mean) in DATA.iter().copied() {
-        let dist = Normal::new(mean, std_devs.sample(&mut rng))
-            .context(anyhow!("creating new normal distribution for {city}"))?;
-        data.push((city, dist));
-    }
-
-    for _ in 0..n {
-        let (city, dist) = data.choose(&mut rng).expect("data is not empty");
-        let temp = dist.sample(&mut rng);
-        let temp = (temp * 10.0).round() / 10.0;
-        writeln!(buf, "{city};{temp}").context("writing data line")?;
-    }
-
-    Ok(())
-}
-
-const DATA: &[(&str, f64)] = &[
+pub const DATA: &[(&str, f64)] = &[
     ("Abha", 18.0),
     ("Abidjan", 26.0),
     ("Abéché", 29.4),
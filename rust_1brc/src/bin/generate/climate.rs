@@ -0,0 +1,31 @@
+//! Seasonal and diurnal swings in temperature, so that rows read like a time-ordered feed.
+//!
+//! Row `n` of the file is taken to be measured at time `n`, and both cycles are sines over that
+//! position, coldest at the start of each year and each day.
+
+use std::f64::consts::TAU;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Climate {
+    /// Amplitude of the yearly cycle, in degrees
+    pub seasonal: f64,
+    pub rows_per_year: f64,
+    /// Amplitude of the daily cycle, in degrees
+    pub diurnal: f64,
+    pub rows_per_day: f64,
+}
+
+impl Climate {
+    /// How far the mean temperature is shifted for the row at `row`.
+    pub fn offset(&self, row: usize) -> f64 {
+        let row = row as f64;
+        let mut offset = 0.0;
+        if self.seasonal != 0.0 {
+            offset -= self.seasonal * (TAU * row / self.rows_per_year).cos();
+        }
+        if self.diurnal != 0.0 {
+            offset -= self.diurnal * (TAU * row / self.rows_per_day).cos();
+        }
+        offset
+    }
+}
@@ -0,0 +1,104 @@
+//! Deliberately broken rows, injected with `--corrupt` to test how solvers handle bad input.
+//!
+//! Each defect replaces one row with one bad line, so a defect's line number is its row number.
+//! The line numbers are listed in a manifest next to the data, and the rows they replace are left
+//! out of the expected output.
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::tenths;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Defect {
+    /// `Hamburg12.3`
+    MissingSeparator,
+    /// `Hamburg;twelve`
+    NotANumber,
+    /// `Hamburg;123.4`
+    OutOfRange,
+    /// A byte that can't appear in UTF-8 after the name
+    InvalidUtf8,
+    /// Nothing at all
+    EmptyLine,
+    /// A good row, ending in `\r\n`
+    Crlf,
+    /// The file ends part way through the last row's name, without a newline
+    TruncatedLastLine,
+}
+
+/// The defects that can replace any row
+const ROW_DEFECTS: [Defect; 6] = [
+    Defect::MissingSeparator,
+    Defect::NotANumber,
+    Defect::OutOfRange,
+    Defect::InvalidUtf8,
+    Defect::EmptyLine,
+    Defect::Crlf,
+];
+
+const NOT_NUMBERS: [&str; 6] = ["", "NaN", "-", "twelve", "12,3", "1.2.3"];
+
+impl Defect {
+    /// A random defect for an ordinary row.
+    pub fn random(rng: &mut impl Rng) -> Self {
+        *ROW_DEFECTS.choose(rng).expect("ROW_DEFECTS is not empty")
+    }
+
+    /// How the defect is listed in the manifest
+    pub fn name(self) -> &'static str {
+        match self {
+            Defect::MissingSeparator => "missing-separator",
+            Defect::NotANumber => "not-a-number",
+            Defect::OutOfRange => "out-of-range",
+            Defect::InvalidUtf8 => "invalid-utf8",
+            Defect::EmptyLine => "empty-line",
+            Defect::Crlf => "crlf",
+            Defect::TruncatedLastLine => "truncated-last-line",
+        }
+    }
+
+    /// Append the broken line for a row that would have been `name;temp`.
+    pub fn push(self, out: &mut Vec<u8>, name: &str, temp: i16, rng: &mut impl Rng) {
+        match self {
+            Defect::MissingSeparator => {
+                out.extend_from_slice(name.as_bytes());
+                tenths::push(out, temp);
+            }
+            Defect::NotANumber => {
+                out.extend_from_slice(name.as_bytes());
+                out.push(b';');
+                let not_number = NOT_NUMBERS.choose(rng).expect("NOT_NUMBERS is not empty");
+                out.extend_from_slice(not_number.as_bytes());
+            }
+            Defect::OutOfRange => {
+                let tenths = rng.gen_range(tenths::LIMIT as i32 + 1..=9999);
+                let sign = if rng.gen() { "-" } else { "" };
+                out.extend_from_slice(name.as_bytes());
+                out.extend_from_slice(format!(";{sign}{}.{}", tenths / 10, tenths % 10).as_bytes());
+            }
+            Defect::InvalidUtf8 => {
+                out.extend_from_slice(name.as_bytes());
+                out.push(0xff);
+                out.push(b';');
+                tenths::push(out, temp);
+            }
+            Defect::EmptyLine => {}
+            Defect::Crlf => {
+                out.extend_from_slice(name.as_bytes());
+                out.push(b';');
+                tenths::push(out, temp);
+                out.push(b'\r');
+            }
+            Defect::TruncatedLastLine => {
+                // keep at least one character, so there is something to see
+                let cut = (1..=name.len() / 2)
+                    .rev()
+                    .find(|&idx| name.is_char_boundary(idx))
+                    .unwrap_or(name.len());
+                out.extend_from_slice(&name.as_bytes()[..cut]);
+                return;
+            }
+        }
+        out.push(b'\n');
+    }
+}
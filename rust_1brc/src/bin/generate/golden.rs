@@ -0,0 +1,101 @@
+//! The expected result for a generated file, tallied exactly while its rows are written.
+//!
+//! Everything is kept in integer tenths of a degree, so the result doesn't depend on the order the
+//! rows are added in or on floating point rounding. It is written in the challenge's own
+//! `{Abha=-23.0/18.0/59.2, ...}` format, so a solver's output can be compared with `cmp`.
+
+use std::{
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Tally {
+    min: i64,
+    max: i64,
+    sum: i64,
+    count: u64,
+}
+
+impl Default for Tally {
+    fn default() -> Self {
+        Self {
+            min: i64::MAX,
+            max: i64::MIN,
+            sum: 0,
+            count: 0,
+        }
+    }
+}
+
+impl Tally {
+    pub fn add(&mut self, tenths: i64) {
+        self.min = self.min.min(tenths);
+        self.max = self.max.max(tenths);
+        self.sum += tenths;
+        self.count += 1;
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+        self.count += other.count;
+    }
+
+    /// The mean in tenths, rounded half up like Java's `Math.round` in the reference solution.
+    fn mean(&self) -> i64 {
+        let count = self.count as i64;
+        (2 * self.sum + count).div_euclid(2 * count)
+    }
+}
+
+/// Merge `other` into `tallies`, station by station.
+pub fn merge_all(tallies: &mut [Tally], other: &[Tally]) {
+    for (tally, other) in tallies.iter_mut().zip(other) {
+        tally.merge(other);
+    }
+}
+
+/// Format tenths of a degree with one decimal, never as `-0.0`.
+fn tenths(value: i64) -> String {
+    let sign = if value < 0 { "-" } else { "" };
+    format!("{sign}{}.{}", value.abs() / 10, value.abs() % 10)
+}
+
+/// Write the expected output for `names[i]` having seen `tallies[i]` to `path`.
+pub fn write(path: &Path, names: &[&str], tallies: &[Tally]) -> Result<()> {
+    let file = std::fs::File::create(path).context("opening expected output for write")?;
+    let mut out = BufWriter::new(file);
+    write_to(&mut out, names, tallies)?;
+    out.flush().context("flushing expected output")
+}
+
+/// Write the expected output for `names[i]` having seen `tallies[i]`, skipping stations that
+/// never came up.
+pub fn write_to(out: &mut impl Write, names: &[&str], tallies: &[Tally]) -> Result<()> {
+    let mut stations = names
+        .iter()
+        .zip(tallies)
+        .filter(|(_, tally)| tally.count > 0)
+        .collect::<Vec<_>>();
+    stations.sort_unstable_by_key(|&(name, _)| *name);
+
+    write!(out, "{{")?;
+    for (idx, (name, tally)) in stations.into_iter().enumerate() {
+        if idx > 0 {
+            write!(out, ", ")?;
+        }
+        write!(
+            out,
+            "{name}={}/{}/{}",
+            tenths(tally.min),
+            tenths(tally.mean()),
+            tenths(tally.max)
+        )?;
+    }
+    writeln!(out, "}}")?;
+    Ok(())
+}
@@ -0,0 +1,740 @@
+//! Generate a file with an arbitrary number of rows.
+//!
+//! See reference implementation: https://github.com/gunnarmorling/1brc/blob/main/src/main/java/dev/morling/onebrc/CreateMeasurements.java
+//!
+//! Runs are reproducible: the same `--seed` and arguments always produce a byte-identical file.
+//! Rows are generated in blocks of [`BLOCK_ROWS`], each drawing from its own stream of the seeded
+//! RNG, so blocks can be formatted on any number of threads without changing the output.
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use rand::{distributions::uniform::SampleUniform, seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution, Normal};
+use rayon::prelude::*;
+use std::{io::Write, path::PathBuf, str::FromStr, sync::mpsc, thread};
+
+/// Rows per independently seeded block
+const BLOCK_ROWS: usize = 256 * 1024;
+
+mod adversarial;
+mod binary;
+mod catalogue;
+mod cities;
+mod climate;
+mod corrupt;
+mod golden;
+mod names;
+mod pick;
+mod target;
+mod tenths;
+
+use adversarial::{Scenario, EXTREMES, TINY_ROWS};
+use catalogue::Entry;
+use cities::DATA;
+use climate::Climate;
+use corrupt::Defect;
+use golden::Tally;
+use pick::{Picker, Skew};
+use target::{Encoding, Target};
+
+/// An inclusive range, given on the command line as `low..high` or a single value.
+#[derive(Debug, Clone, Copy)]
+struct Span<T> {
+    low: T,
+    high: T,
+}
+
+impl<T: Copy> Span<T> {
+    fn exactly(value: T) -> Self {
+        Self {
+            low: value,
+            high: value,
+        }
+    }
+}
+
+impl<T> FromStr for Span<T>
+where
+    T: FromStr + PartialOrd,
+    T::Err: std::fmt::Display,
+{
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (low, high) = s.split_once("..").unwrap_or((s, s));
+        let low = low
+            .trim()
+            .parse()
+            .map_err(|err| anyhow!("parsing range start: {err}"))?;
+        let high = high
+            .trim()
+            .parse()
+            .map_err(|err| anyhow!("parsing range end: {err}"))?;
+        if low > high {
+            bail!("range {s} is empty");
+        }
+        Ok(Self { low, high })
+    }
+}
+
+impl<T: SampleUniform + PartialOrd + Copy> Span<T> {
+    fn sample(&self, rng: &mut impl Rng) -> T {
+        if self.low == self.high {
+            self.low
+        } else {
+            rng.gen_range(self.low..=self.high)
+        }
+    }
+}
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// How many rows to generate
+    rows: usize,
+
+    /// Where to write the measurements: in the binary format for `.bin`, and compressed if this
+    /// ends in `.gz` or `.zst`. The expected result is written next to it, with the extension
+    /// changed to `.out`
+    #[clap(short, long, default_value = "../measurements.txt")]
+    output: PathBuf,
+
+    /// Seed for the random number generator; a random seed is chosen and printed if omitted
+    #[clap(long)]
+    seed: Option<u64>,
+
+    /// Only use this many stations, picked at random from the built-in list
+    #[clap(long, conflicts_with_all = ["synthetic", "adversarial", "catalogue"])]
+    stations: Option<usize>,
+
+    /// Make up this many distinct station names instead of using the real cities, as in the 10K
+    /// variant of the challenge
+    #[clap(long, conflicts_with_all = ["adversarial", "catalogue"])]
+    synthetic: Option<usize>,
+
+    /// Range the length of synthesized names is drawn from, in bytes of UTF-8
+    #[clap(long, default_value = "1..100", requires = "synthetic")]
+    name_bytes: Span<usize>,
+
+    /// Chance that each character of a synthesized name is multi-byte
+    #[clap(long, default_value_t = 0.1, requires = "synthetic")]
+    multibyte: f64,
+
+    /// Range each station's standard deviation is drawn from, e.g. `5..15`, or `10` for all
+    #[clap(long, default_value = "5..15")]
+    std_dev: Span<f64>,
+
+    /// Generate a dataset aimed at one of the solvers' weak spots
+    #[clap(long, value_enum, conflicts_with = "catalogue")]
+    adversarial: Option<Scenario>,
+
+    /// Read the stations from a file of `name;mean[;stddev[;weight]]` lines instead
+    #[clap(long)]
+    catalogue: Option<PathBuf>,
+
+    /// How rows are spread over the stations: `uniform`, `zipf:<s>` for a Zipf distribution with
+    /// exponent `s`, or `hot:<k>` for `k` stations taking 90% of the rows. Multiplies any weights
+    /// from the catalogue
+    #[clap(long, default_value = "uniform")]
+    distribution: Skew,
+
+    /// Amplitude of the yearly temperature cycle over the rows, in degrees
+    #[clap(long, default_value_t = 0.0)]
+    seasonal: f64,
+
+    /// Amplitude of the daily temperature cycle over the rows, in degrees
+    #[clap(long, default_value_t = 0.0)]
+    diurnal: f64,
+
+    /// Rows per simulated year; defaults to the whole file being one year
+    #[clap(long)]
+    rows_per_year: Option<usize>,
+
+    /// Rows per simulated day; defaults to a 365th of a year
+    #[clap(long)]
+    rows_per_day: Option<usize>,
+
+    /// Chance of each row being replaced by a broken line, listed by line number in a manifest
+    /// with the extension changed to `.defects`. The last line is then also cut short
+    #[clap(long, default_value_t = 0.0)]
+    corrupt: f64,
+
+    /// Split the rows over this many files, named like `measurements-0.txt`, without breaking any
+    /// lines
+    #[clap(long, default_value_t = 1)]
+    shards: usize,
+
+    /// Threads formatting blocks of rows; defaults to one per core
+    #[clap(short = 'j', long)]
+    threads: Option<usize>,
+}
+
+/// The RNG for one block of rows.
+///
+/// Stream 0 is used to set up the stations, and block `n` uses stream `n + 1`, so every block's
+/// numbers depend only on the master seed and the block's index.
+fn block_rng(seed: u64, block: usize) -> ChaCha8Rng {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(block as u64 + 1);
+    rng
+}
+
+/// A station and the distribution its temperatures are drawn from
+struct Station {
+    name: String,
+    dist: Normal<f64>,
+}
+
+/// Everything needed to generate any block of rows
+struct Dataset {
+    stations: Vec<Station>,
+    picker: Picker,
+    climate: Climate,
+    /// Chance of a row taking one of [`EXTREMES`] instead of a draw from its station
+    extremes: f64,
+    /// Chance of a row being replaced by a [`Defect`]
+    corrupt: f64,
+    rows: usize,
+    /// Rows per independently seeded block, [`BLOCK_ROWS`] unless a test needs smaller ones
+    block_rows: usize,
+}
+
+/// One block of formatted rows
+struct Block {
+    data: Vec<u8>,
+    /// The temperatures of each station in the block, leaving out defects
+    tallies: Vec<Tally>,
+    /// The line number of each defect, counting from 1 at the start of the file
+    defects: Vec<(usize, Defect)>,
+}
+
+impl Dataset {
+    /// Format one block of rows, tallying the good ones and noting where the defects went.
+    fn format_block(&self, seed: u64, block: usize, rows: usize, encoding: Encoding) -> Block {
+        let mut rng = block_rng(seed, block);
+        let mut out = Vec::with_capacity(rows * 16);
+        let mut tallies = vec![Tally::default(); self.stations.len()];
+        let mut defects = Vec::new();
+        for row in block * self.block_rows..block * self.block_rows + rows {
+            let idx = self.picker.pick(&mut rng);
+            let station = &self.stations[idx];
+            let temp = if self.extremes > 0.0 && rng.gen_bool(self.extremes) {
+                *EXTREMES.choose(&mut rng).expect("EXTREMES is not empty")
+            } else {
+                let degrees = station.dist.sample(&mut rng) + self.climate.offset(row);
+                tenths::from_degrees(degrees)
+            };
+
+            if self.corrupt > 0.0 {
+                let defect = if row + 1 == self.rows {
+                    Some(Defect::TruncatedLastLine)
+                } else if rng.gen_bool(self.corrupt) {
+                    Some(Defect::random(&mut rng))
+                } else {
+                    None
+                };
+                if let Some(defect) = defect {
+                    defect.push(&mut out, &station.name, temp, &mut rng);
+                    defects.push((row + 1, defect));
+                    continue;
+                }
+            }
+
+            tallies[idx].add(tenths::value(temp));
+            match encoding {
+                Encoding::Text => {
+                    out.extend_from_slice(station.name.as_bytes());
+                    out.push(b';');
+                    tenths::push(&mut out, temp);
+                    out.push(b'\n');
+                }
+                Encoding::Binary => binary::push(&mut out, idx, temp),
+            }
+        }
+        Block {
+            data: out,
+            tallies,
+            defects,
+        }
+    }
+}
+
+/// Give made-up stations means across the same range as the real cities.
+fn with_means(rng: &mut impl Rng, names: Vec<String>) -> Vec<(String, f64)> {
+    let (coldest, hottest) = DATA.iter().fold(
+        (f64::INFINITY, f64::NEG_INFINITY),
+        |(coldest, hottest), &(_, mean)| (coldest.min(mean), hottest.max(mean)),
+    );
+    names
+        .into_iter()
+        .map(|name| (name, rng.gen_range(coldest..=hottest)))
+        .collect()
+}
+
+/// The stations to use without a catalogue: the built-in cities, made up ones, or those of an
+/// adversarial scenario, each with its mean temperature.
+fn stations(args: &Args, rng: &mut impl Rng) -> Result<Vec<(String, f64)>> {
+    Ok(match (args.adversarial, args.stations, args.synthetic) {
+        (Some(scenario), _, _) => scenario.stations(rng)?,
+        (None, _, Some(n)) => {
+            if !(0.0..=1.0).contains(&args.multibyte) {
+                bail!("--multibyte must be between 0 and 1");
+            }
+            let names = names::synthesize(rng, n, args.name_bytes, args.multibyte)?;
+            with_means(rng, names)
+        }
+        (None, None, None) => DATA
+            .iter()
+            .map(|&(city, mean)| (city.to_owned(), mean))
+            .collect(),
+        (None, Some(n), None) if n == 0 || n > DATA.len() => {
+            bail!("--stations must be between 1 and {}", DATA.len())
+        }
+        (None, Some(n), None) => DATA
+            .choose_multiple(rng, n)
+            .map(|&(city, mean)| (city.to_owned(), mean))
+            .collect(),
+    })
+}
+
+/// Format every row of `dataset` on `pool` and write them to `shards`, listing any defects in
+/// `manifest`. Returns the tally of every station.
+///
+/// Blocks are formatted a batch at a time while the previous batch is written, and always written
+/// in order, so the output only depends on `seed` and not on the size of `pool`.
+fn generate(
+    dataset: &Dataset,
+    seed: u64,
+    target: &Target,
+    pool: &rayon::ThreadPool,
+    shards: &mut [impl Write + Send],
+    mut manifest: Option<&mut (impl Write + Send)>,
+) -> Result<Vec<Tally>> {
+    // every shard gets the header, so that it can be read on its own
+    let header = match target.encoding() {
+        Encoding::Text => Vec::new(),
+        Encoding::Binary => {
+            let names = dataset
+                .stations
+                .iter()
+                .map(|station| station.name.as_str())
+                .collect::<Vec<_>>();
+            target.compress(&binary::header(&names)?)?
+        }
+    };
+    for shard in shards.iter_mut() {
+        shard.write_all(&header).context("writing header")?;
+    }
+
+    let blocks = dataset.rows.div_ceil(dataset.block_rows);
+    let batch = pool.current_num_threads() * 4;
+    thread::scope(|scope| {
+        let (tx, rx) = mpsc::sync_channel::<Vec<(Vec<(usize, Vec<u8>)>, Block)>>(1);
+        let stations = dataset.stations.len();
+        let writer = scope.spawn(move || -> Result<Vec<Tally>> {
+            let mut tallies = vec![Tally::default(); stations];
+            for formatted in rx {
+                for (pieces, block) in formatted {
+                    for (shard, piece) in pieces {
+                        shards[shard]
+                            .write_all(&piece)
+                            .context("writing data block")?;
+                    }
+                    golden::merge_all(&mut tallies, &block.tallies);
+                    if let Some(manifest) = manifest.as_mut() {
+                        for (line, defect) in block.defects {
+                            writeln!(manifest, "{line};{}", defect.name())
+                                .context("writing defect manifest")?;
+                        }
+                    }
+                }
+            }
+            for shard in shards.iter_mut() {
+                shard.flush().context("flushing output file")?;
+            }
+            if let Some(manifest) = manifest.as_mut() {
+                manifest.flush().context("flushing defect manifest")?;
+            }
+            Ok(tallies)
+        });
+
+        for first in (0..blocks).step_by(batch) {
+            let formatted = pool.install(|| {
+                (first..(first + batch).min(blocks))
+                    .into_par_iter()
+                    .map(|block| {
+                        let first_row = block * dataset.block_rows;
+                        let rows = dataset.block_rows.min(dataset.rows - first_row);
+                        let mut formatted =
+                            dataset.format_block(seed, block, rows, target.encoding());
+                        let data = std::mem::take(&mut formatted.data);
+                        Ok((target.pieces(first_row, data)?, formatted))
+                    })
+                    .collect::<Result<_>>()
+            })?;
+            if tx.send(formatted).is_err() {
+                // the writer failed; its error is reported below
+                break;
+            }
+        }
+        drop(tx);
+
+        writer.join().expect("writer thread does not panic")
+    })
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let seed = args.seed.unwrap_or_else(rand::random);
+    eprintln!("seed: {seed}");
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    let entries = match &args.catalogue {
+        Some(path) => catalogue::load(path)?,
+        None => stations(&args, &mut rng)?
+            .into_iter()
+            .map(|(name, mean)| Entry::new(name, mean))
+            .collect(),
+    };
+    if entries.is_empty() {
+        bail!("need at least one station");
+    }
+
+    let weights = args
+        .distribution
+        .weights(entries.len(), &mut rng)
+        .into_iter()
+        .zip(&entries)
+        .map(|(skew, entry)| skew * entry.weight)
+        .collect::<Vec<_>>();
+    let mut stations = Vec::with_capacity(entries.len());
+    for Entry {
+        name,
+        mean,
+        std_dev,
+        ..
+    } in entries
+    {
+        let std_dev = std_dev.unwrap_or_else(|| args.std_dev.sample(&mut rng));
+        let dist = Normal::new(mean, std_dev)
+            .context(anyhow!("creating new normal distribution for {name}"))?;
+        stations.push(Station { name, dist });
+    }
+
+    let rows = if args.adversarial == Some(Scenario::Tiny) {
+        args.rows.min(TINY_ROWS)
+    } else {
+        args.rows
+    };
+    let rows_per_year = args.rows_per_year.unwrap_or(rows.max(1));
+    let climate = Climate {
+        seasonal: args.seasonal,
+        rows_per_year: rows_per_year as f64,
+        diurnal: args.diurnal,
+        rows_per_day: args
+            .rows_per_day
+            .map_or(rows_per_year as f64 / 365.0, |rows| rows as f64),
+    };
+
+    let dataset = Dataset {
+        stations,
+        picker: Picker::new(&weights)?,
+        climate,
+        extremes: if args.adversarial == Some(Scenario::Extremes) {
+            1.0 / 3.0
+        } else {
+            0.0
+        },
+        corrupt: args.corrupt,
+        rows,
+        block_rows: BLOCK_ROWS,
+    };
+    if !(0.0..=1.0).contains(&args.corrupt) {
+        bail!("--corrupt must be between 0 and 1");
+    }
+
+    if args.shards == 0 {
+        bail!("--shards must be at least 1");
+    }
+    let target = Target::new(&args.output, args.shards, rows);
+    if args.corrupt > 0.0 && target.encoding() == Encoding::Binary {
+        bail!("--corrupt only applies to text output");
+    }
+    let mut shards = target.create()?;
+    let mut manifest = if args.corrupt > 0.0 {
+        let file = std::fs::File::create(target.sidecar("defects"))
+            .context("opening defect manifest for write")?;
+        Some(std::io::BufWriter::new(file))
+    } else {
+        None
+    };
+
+    let mut pool = rayon::ThreadPoolBuilder::new();
+    if let Some(threads) = args.threads {
+        pool = pool.num_threads(threads);
+    }
+    let pool = pool.build().context("building thread pool")?;
+
+    let tallies = generate(
+        &dataset,
+        seed,
+        &target,
+        &pool,
+        &mut shards,
+        manifest.as_mut(),
+    )?;
+
+    let names = dataset
+        .stations
+        .iter()
+        .map(|station| station.name.as_str())
+        .collect::<Vec<_>>();
+    golden::write(&target.sidecar("out"), &names, &tallies)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::*;
+
+    const SEED: u64 = 0x1b_7c;
+
+    /// `rows` rows over the built-in cities, in blocks small enough for several threads to share.
+    fn dataset(rows: usize, corrupt: f64) -> Dataset {
+        let stations = DATA
+            .iter()
+            .map(|&(name, mean)| Station {
+                name: name.to_owned(),
+                dist: Normal::new(mean, 10.0).unwrap(),
+            })
+            .collect::<Vec<_>>();
+        Dataset {
+            picker: Picker::new(&vec![1.0; stations.len()]).unwrap(),
+            stations,
+            climate: Climate {
+                seasonal: 0.0,
+                rows_per_year: rows as f64,
+                diurnal: 0.0,
+                rows_per_day: 1.0,
+            },
+            extremes: 0.0,
+            corrupt,
+            rows,
+            block_rows: 300,
+        }
+    }
+
+    /// Generate `dataset` into memory on `threads` threads: the bytes of each of `shards` shards
+    /// of `output`, the defect manifest and the tallies.
+    fn run(
+        dataset: &Dataset,
+        output: &str,
+        shards: usize,
+        threads: usize,
+    ) -> (Vec<Vec<u8>>, Vec<u8>, Vec<Tally>) {
+        let target = Target::new(Path::new(output), shards, dataset.rows);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        let mut data = vec![Vec::new(); shards];
+        let mut manifest = Vec::new();
+        let tallies = generate(
+            dataset,
+            SEED,
+            &target,
+            &pool,
+            &mut data,
+            Some(&mut manifest),
+        )
+        .unwrap();
+        (data, manifest, tallies)
+    }
+
+    #[test]
+    fn output_does_not_depend_on_threads() {
+        let dataset = dataset(5_000, 0.01);
+        let (data, manifest, _) = run(&dataset, "measurements.txt", 1, 1);
+        assert_eq!(data[0].iter().filter(|&&b| b == b'\n').count(), 4_999);
+        for threads in [2, 5] {
+            let (other_data, other_manifest, _) = run(&dataset, "measurements.txt", 1, threads);
+            assert!(data == other_data, "data with {threads} threads");
+            assert_eq!(manifest, other_manifest, "manifest with {threads} threads");
+        }
+    }
+
+    /// Aggregate text measurements the slow way, straight into the expected output format.
+    fn aggregate(data: &[u8]) -> String {
+        let mut stations = std::collections::BTreeMap::<&str, (i64, i64, i64, i64)>::new();
+        for line in std::str::from_utf8(data).unwrap().lines() {
+            let (name, temp) = line.split_once(';').unwrap();
+            let tenths = temp.replace('.', "").parse::<i64>().unwrap();
+            let (min, max, sum, count) = stations.entry(name).or_insert((i64::MAX, i64::MIN, 0, 0));
+            *min = tenths.min(*min);
+            *max = tenths.max(*max);
+            *sum += tenths;
+            *count += 1;
+        }
+
+        let format = |tenths: i64| {
+            let sign = if tenths < 0 { "-" } else { "" };
+            format!("{sign}{}.{}", tenths.abs() / 10, tenths.abs() % 10)
+        };
+        let stations = stations
+            .into_iter()
+            .map(|(name, (min, max, sum, count))| {
+                // rounded half up, in tenths
+                let mean = (2 * sum + count).div_euclid(2 * count);
+                format!("{name}={}/{}/{}", format(min), format(mean), format(max))
+            })
+            .collect::<Vec<_>>();
+        format!("{{{}}}\n", stations.join(", "))
+    }
+
+    #[test]
+    fn expected_output_matches_the_data() {
+        let dataset = dataset(5_000, 0.0);
+        let (data, _, tallies) = run(&dataset, "measurements.txt", 1, 3);
+
+        let names = dataset
+            .stations
+            .iter()
+            .map(|station| station.name.as_str())
+            .collect::<Vec<_>>();
+        let mut expected = Vec::new();
+        golden::write_to(&mut expected, &names, &tallies).unwrap();
+        assert_eq!(String::from_utf8(expected).unwrap(), aggregate(&data[0]));
+    }
+
+    /// Whether `line` is a row a solver should accept: a known station and a legal temperature.
+    fn well_formed(line: &[u8], names: &[&str]) -> bool {
+        let Some((name, temp)) = std::str::from_utf8(line)
+            .ok()
+            .and_then(|line| line.split_once(';'))
+        else {
+            return false;
+        };
+        let digits = temp.strip_prefix('-').unwrap_or(temp);
+        let legal = matches!(digits.split_once('.'), Some((int, frac))
+            if (1..=2).contains(&int.len())
+                && frac.len() == 1
+                && digits.bytes().all(|b| b.is_ascii_digit() || b == b'.'));
+        legal && names.contains(&name)
+    }
+
+    #[test]
+    fn manifest_lists_exactly_the_defects() {
+        let dataset = dataset(5_000, 0.05);
+        let (data, manifest, tallies) = run(&dataset, "measurements.txt", 1, 2);
+        let names = dataset
+            .stations
+            .iter()
+            .map(|station| station.name.as_str())
+            .collect::<Vec<_>>();
+
+        let listed = std::str::from_utf8(&manifest)
+            .unwrap()
+            .lines()
+            .map(|entry| entry.split_once(';').unwrap().0.parse::<usize>().unwrap())
+            .collect::<std::collections::BTreeSet<_>>();
+        assert!(listed.len() > 100, "{} defects", listed.len());
+        assert!(listed.contains(&5_000), "the last line is cut short");
+
+        // the last line has no newline, so splitting gives exactly one piece per row
+        let lines = data[0].split(|&b| b == b'\n').collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5_000);
+        let mut good = Vec::new();
+        for (idx, line) in lines.into_iter().enumerate() {
+            assert_eq!(
+                !well_formed(line, &names),
+                listed.contains(&(idx + 1)),
+                "line {}: {:?}",
+                idx + 1,
+                String::from_utf8_lossy(line)
+            );
+            if well_formed(line, &names) {
+                good.extend_from_slice(line);
+                good.push(b'\n');
+            }
+        }
+
+        // and only the good lines are in the expected output
+        let mut expected = Vec::new();
+        golden::write_to(&mut expected, &names, &tallies).unwrap();
+        assert_eq!(String::from_utf8(expected).unwrap(), aggregate(&good));
+    }
+
+    /// Read a file in the binary format back as text rows.
+    fn binary_to_text(data: &[u8]) -> Vec<u8> {
+        assert_eq!(&data[..4], binary::MAGIC);
+        assert_eq!(data[4], binary::VERSION);
+        let stations = u32::from_le_bytes(data[5..9].try_into().unwrap()) as usize;
+        let mut names = Vec::with_capacity(stations);
+        let mut at = 9;
+        for _ in 0..stations {
+            let len = data[at] as usize;
+            names.push(&data[at + 1..at + 1 + len]);
+            at += 1 + len;
+        }
+
+        let rows = &data[at..];
+        assert_eq!(rows.len() % binary::ROW_BYTES, 0);
+        let mut text = Vec::new();
+        for row in rows.chunks_exact(binary::ROW_BYTES) {
+            let station = u16::from_le_bytes([row[0], row[1]]) as usize;
+            text.extend_from_slice(names[station]);
+            text.push(b';');
+            tenths::push(&mut text, i16::from_le_bytes([row[2], row[3]]));
+            text.push(b'\n');
+        }
+        text
+    }
+
+    #[test]
+    fn every_target_holds_the_plain_text() {
+        use std::io::Read;
+
+        let dataset = dataset(5_000, 0.0);
+        let (plain, _, _) = run(&dataset, "measurements.txt", 1, 2);
+        let plain = &plain[0];
+
+        for output in [
+            "measurements.txt",
+            "measurements.txt.gz",
+            "measurements.txt.zst",
+            "measurements.bin",
+            "measurements.bin.gz",
+            "measurements.bin.zst",
+        ] {
+            for shards in [1, 3] {
+                let (data, _, _) = run(&dataset, output, shards, 2);
+                let mut text = Vec::new();
+                for shard in data {
+                    let shard = if output.ends_with(".gz") {
+                        let mut decoded = Vec::new();
+                        flate2::read::MultiGzDecoder::new(shard.as_slice())
+                            .read_to_end(&mut decoded)
+                            .unwrap();
+                        decoded
+                    } else if output.ends_with(".zst") {
+                        zstd::decode_all(shard.as_slice()).unwrap()
+                    } else {
+                        shard
+                    };
+                    let shard = if output.contains(".bin") {
+                        binary_to_text(&shard)
+                    } else {
+                        shard
+                    };
+                    assert_eq!(shard.last(), Some(&b'\n'), "{output} in {shards} shards");
+                    text.extend_from_slice(&shard);
+                }
+                assert!(text == *plain, "{output} in {shards} shards");
+            }
+        }
+    }
+}
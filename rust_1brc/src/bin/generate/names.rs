@@ -0,0 +1,75 @@
+//! Synthesized station names for the 10K variant of the challenge, which allows up to 10,000
+//! distinct names of 1 to 100 bytes of UTF-8.
+
+use anyhow::{bail, Result};
+use rand::Rng;
+use rustc_hash::FxHashSet;
+
+use crate::Span;
+
+const ASCII: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ '-.";
+
+/// A random character that takes up `width` bytes of UTF-8.
+fn multibyte_char(rng: &mut impl Rng, width: usize) -> char {
+    let code = match width {
+        // Latin-1 supplement and Latin Extended-A
+        2 => rng.gen_range(0xc0..=0x17f),
+        // CJK unified ideographs
+        3 => rng.gen_range(0x4e00..=0x9fff),
+        // emoticons
+        _ => rng.gen_range(0x1f600..=0x1f64f),
+    };
+    char::from_u32(code).expect("ranges above are all valid chars")
+}
+
+/// A name of exactly `bytes` bytes, where each character is multi-byte with probability
+/// `multibyte`.
+pub fn name(rng: &mut impl Rng, bytes: usize, multibyte: f64) -> String {
+    let mut name = String::with_capacity(bytes);
+    while name.len() < bytes {
+        let remaining = bytes - name.len();
+        if remaining >= 2 && rng.gen_bool(multibyte) {
+            let width = rng.gen_range(2..=remaining.min(4));
+            name.push(multibyte_char(rng, width));
+        } else {
+            // keep spaces and punctuation away from the start so names read like names
+            let alphabet = if name.is_empty() { &ASCII[..52] } else { ASCII };
+            name.push(alphabet[rng.gen_range(0..alphabet.len())] as char);
+        }
+    }
+    name
+}
+
+/// `count` distinct names, with lengths in bytes drawn uniformly from `bytes`.
+pub fn synthesize(
+    rng: &mut impl Rng,
+    count: usize,
+    bytes: Span<usize>,
+    multibyte: f64,
+) -> Result<Vec<String>> {
+    if bytes.low == 0 {
+        bail!("station names must be at least one byte long");
+    }
+
+    let mut seen = FxHashSet::default();
+    let mut names = Vec::with_capacity(count);
+    let mut attempts = 0;
+    while names.len() < count {
+        attempts += 1;
+        if attempts > count * 100 {
+            bail!(
+                "could only find {} distinct names of {}..{} bytes, not {count}",
+                names.len(),
+                bytes.low,
+                bytes.high
+            );
+        }
+
+        let len = bytes.sample(rng);
+        let name = name(rng, len, multibyte);
+        if seen.insert(name.clone()) {
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
@@ -0,0 +1,96 @@
+//! Choosing which station each row comes from.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context, Result};
+use rand::{distributions::WeightedIndex, seq::SliceRandom, Rng};
+use rand_distr::Distribution;
+
+/// Share of the rows that go to the `k` stations of [`Skew::Hot`]
+const HOT_SHARE: f64 = 0.9;
+
+/// How unevenly rows are spread over the stations, given as `uniform`, `zipf:s` or `hot:k`.
+#[derive(Debug, Clone, Copy)]
+pub enum Skew {
+    /// Every station equally often
+    Uniform,
+    /// The `r`th most common station in proportion to `1 / r^s`
+    Zipf(f64),
+    /// `k` stations get 90% of the rows between them
+    Hot(usize),
+}
+
+impl FromStr for Skew {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (kind, param) = s.split_once(':').unwrap_or((s, ""));
+        match (kind, param) {
+            ("uniform", "") => Ok(Skew::Uniform),
+            ("zipf", s) => {
+                let s: f64 = s.parse().context("parsing zipf exponent")?;
+                if !(0.0..f64::INFINITY).contains(&s) {
+                    bail!("zipf exponent must be finite and not negative");
+                }
+                Ok(Skew::Zipf(s))
+            }
+            ("hot", k) => match k.parse().context("parsing number of hot stations")? {
+                0 => bail!("need at least one hot station"),
+                k => Ok(Skew::Hot(k)),
+            },
+            _ => Err(anyhow!("expected uniform, zipf:<s> or hot:<k>, not {s:?}")),
+        }
+    }
+}
+
+impl Skew {
+    /// Relative weights for `stations` stations. Which stations are the common ones is shuffled
+    /// with `rng`, so it has nothing to do with their order.
+    pub fn weights(self, stations: usize, rng: &mut impl Rng) -> Vec<f64> {
+        let mut weights = match self {
+            Skew::Uniform => return vec![1.0; stations],
+            Skew::Zipf(s) => (1..=stations)
+                .map(|rank| (rank as f64).powf(-s))
+                .collect::<Vec<_>>(),
+            Skew::Hot(k) if k >= stations => return vec![1.0; stations],
+            Skew::Hot(k) => (0..stations)
+                .map(|idx| {
+                    if idx < k {
+                        HOT_SHARE / k as f64
+                    } else {
+                        (1.0 - HOT_SHARE) / (stations - k) as f64
+                    }
+                })
+                .collect(),
+        };
+        weights.shuffle(rng);
+        weights
+    }
+}
+
+pub enum Picker {
+    /// Every station equally often
+    Uniform(usize),
+    /// Stations in proportion to their weights
+    Weighted(WeightedIndex<f64>),
+}
+
+impl Picker {
+    pub fn new(weights: &[f64]) -> Result<Self> {
+        if weights.windows(2).all(|pair| pair[0] == pair[1]) {
+            Ok(Picker::Uniform(weights.len()))
+        } else {
+            Ok(Picker::Weighted(
+                WeightedIndex::new(weights).context("station weights")?,
+            ))
+        }
+    }
+
+    /// The index of the station the next row comes from.
+    pub fn pick(&self, rng: &mut impl Rng) -> usize {
+        match self {
+            Picker::Uniform(stations) => rng.gen_range(0..*stations),
+            Picker::Weighted(weights) => weights.sample(rng),
+        }
+    }
+}
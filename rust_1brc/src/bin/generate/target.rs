@@ -0,0 +1,194 @@
+//! Where the rows go: one file or several row-aligned shards, as text or in the binary format,
+//! optionally compressed.
+//!
+//! Compression is picked from the extension of `--output`: `.gz` for gzip and `.zst` for zstd. The
+//! extension before that picks the [`binary`](crate::binary) format for `.bin`, and text otherwise.
+//! Every block is compressed on its own, by the thread that formatted it, into a separate gzip
+//! member or zstd frame. Both formats allow those to be concatenated, so the result is an ordinary
+//! compressed file, and it is still byte-identical whatever the number of threads.
+
+use std::{
+    ffi::OsStr,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use flate2::{write::GzEncoder, Compression as GzLevel};
+
+use crate::binary;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    fn compress(self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(match self {
+            Compression::None => data.to_vec(),
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), GzLevel::default());
+                encoder.write_all(data)?;
+                encoder.finish()?
+            }
+            Compression::Zstd => zstd::encode_all(data, zstd::DEFAULT_COMPRESSION_LEVEL)?,
+        })
+    }
+}
+
+/// How rows are written out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// `name;temp` lines, as the challenge reads them
+    Text,
+    /// The [`binary`] format
+    Binary,
+}
+
+pub struct Target {
+    /// The output path without any compression extension, e.g. `measurements.txt`
+    plain: PathBuf,
+    encoding: Encoding,
+    compression: Compression,
+    shards: usize,
+    rows: usize,
+}
+
+impl Target {
+    pub fn new(output: &Path, shards: usize, rows: usize) -> Self {
+        let compression = match output.extension().and_then(OsStr::to_str) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        };
+        let plain = match compression {
+            Compression::None => output.to_owned(),
+            _ => output.with_extension(""),
+        };
+        let encoding = match plain.extension().and_then(OsStr::to_str) {
+            Some("bin") => Encoding::Binary,
+            _ => Encoding::Text,
+        };
+        Self {
+            plain,
+            encoding,
+            compression,
+            shards,
+            rows,
+        }
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Compress `data` to be written on its own, as the output's extension asks.
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        self.compression.compress(data)
+    }
+
+    /// The output path with `ext` in place of its (uncompressed) extension, for the files that go
+    /// alongside the data.
+    pub fn sidecar(&self, ext: &str) -> PathBuf {
+        self.plain.with_extension(ext)
+    }
+
+    /// Where shard `shard` is written: `measurements-3.txt.gz` for `measurements.txt.gz`, or just
+    /// the output path without sharding.
+    pub fn shard_path(&self, shard: usize) -> PathBuf {
+        let mut path = self.plain.clone();
+        if self.shards > 1 {
+            let width = (self.shards - 1).to_string().len();
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let mut name = format!("{stem}-{shard:0width$}");
+            if let Some(ext) = path.extension() {
+                name = format!("{name}.{}", ext.to_string_lossy());
+            }
+            path.set_file_name(name);
+        }
+        match self.compression {
+            Compression::None => path,
+            Compression::Gzip => path.with_extension(append_ext(&path, "gz")),
+            Compression::Zstd => path.with_extension(append_ext(&path, "zst")),
+        }
+    }
+
+    /// The first row of `shard`; one past the last row for `shard == shards`.
+    fn shard_start(&self, shard: usize) -> usize {
+        shard * self.rows / self.shards
+    }
+
+    /// The shard `row` goes into, skipping over shards too small to get any rows.
+    fn shard_of(&self, row: usize) -> usize {
+        (0..self.shards)
+            .rev()
+            .find(|&shard| self.shard_start(shard) <= row)
+            .unwrap_or(0)
+    }
+
+    /// Split a block of `data`, starting with row `first_row`, at shard boundaries and compress
+    /// each piece. Returns the shard each piece belongs to along with its bytes.
+    pub fn pieces(&self, first_row: usize, data: Vec<u8>) -> Result<Vec<(usize, Vec<u8>)>> {
+        let mut shard = self.shard_of(first_row);
+        if self.shards == 1 && self.compression == Compression::None {
+            return Ok(vec![(shard, data)]);
+        }
+
+        let mut pieces = Vec::new();
+        let mut start = 0;
+        if self.shards > 1 {
+            // every row is one line of text or one fixed-size record, so each shard starts after
+            // a known number of row ends
+            let row_ends: Box<dyn Iterator<Item = usize>> = match self.encoding {
+                Encoding::Text => Box::new(
+                    data.iter()
+                        .enumerate()
+                        .filter(|&(_, &b)| b == b'\n')
+                        .map(|(idx, _)| idx + 1),
+                ),
+                Encoding::Binary => Box::new(
+                    (1..=data.len() / binary::ROW_BYTES).map(|row| row * binary::ROW_BYTES),
+                ),
+            };
+            let mut row = first_row;
+            let mut next = self.shard_start(shard + 1);
+            for end in row_ends {
+                row += 1;
+                if row == next && row < self.rows {
+                    pieces.push((shard, self.compression.compress(&data[start..end])?));
+                    start = end;
+                    shard = self.shard_of(row);
+                    next = self.shard_start(shard + 1);
+                }
+            }
+        }
+        if start < data.len() {
+            pieces.push((shard, self.compression.compress(&data[start..])?));
+        }
+        Ok(pieces)
+    }
+
+    /// Open the writer for every shard. Shards that no rows fall into are left empty.
+    pub fn create(&self) -> Result<Vec<BufWriter<File>>> {
+        (0..self.shards)
+            .map(|shard| {
+                let path = self.shard_path(shard);
+                let file = File::create(&path)
+                    .with_context(|| format!("opening {} for write", path.display()))?;
+                Ok(BufWriter::new(file))
+            })
+            .collect()
+    }
+}
+
+/// `path`'s extension with `ext` appended, e.g. `txt.gz`
+fn append_ext(path: &Path, ext: &str) -> String {
+    match path.extension() {
+        Some(existing) => format!("{}.{ext}", existing.to_string_lossy()),
+        None => ext.to_owned(),
+    }
+}
@@ -0,0 +1,37 @@
+//! Temperatures as integer tenths of a degree, and writing them in the input format.
+
+/// The legal range of temperatures, -99.9 to 99.9
+pub const LIMIT: i16 = 999;
+
+/// Stands in for -0.0, which the input format allows but an integer can't tell apart from 0.0
+pub const NEGATIVE_ZERO: i16 = i16::MIN;
+
+/// Round `degrees` to tenths, clamped to the legal range.
+pub fn from_degrees(degrees: f64) -> i16 {
+    (degrees * 10.0).round().clamp(-LIMIT as f64, LIMIT as f64) as i16
+}
+
+/// The value of `tenths` for aggregating, with [`NEGATIVE_ZERO`] as plain 0.
+pub fn value(tenths: i16) -> i64 {
+    if tenths == NEGATIVE_ZERO {
+        0
+    } else {
+        tenths as i64
+    }
+}
+
+/// Append `tenths` with exactly one decimal, e.g. `-12.3`, `0.5` or `99.9`.
+pub fn push(out: &mut Vec<u8>, tenths: i16) {
+    if tenths == NEGATIVE_ZERO {
+        out.extend_from_slice(b"-0.0");
+        return;
+    }
+    if tenths < 0 {
+        out.push(b'-');
+    }
+    let abs = tenths.unsigned_abs();
+    if abs >= 100 {
+        out.push(b'0' + (abs / 100) as u8);
+    }
+    out.extend_from_slice(&[b'0' + (abs / 10 % 10) as u8, b'.', b'0' + (abs % 10) as u8]);
+}
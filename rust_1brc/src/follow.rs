@@ -0,0 +1,375 @@
+//! `1brc follow <file>`: tail a measurements file that is still being written.
+//!
+//! The parent directory is watched with inotify rather than the file itself, so that we also see a
+//! rotated file being replaced by a fresh one under the same name. Each wake-up only reads the bytes
+//! appended since the last one; complete lines are folded into a map that lives for the whole run,
+//! and a trailing partial line is held back until its newline arrives.
+
+use std::{
+    fs::File,
+    io::{IsTerminal, Write},
+    os::unix::fs::{FileExt, MetadataExt},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use inotify::{Inotify, WatchMask};
+
+use crate::{
+    output::{self, Format},
+    parse_record, stations_from_map, Map, Records, Result, CHUNK_SIZE,
+};
+
+struct Follower {
+    path: PathBuf,
+    file: Option<File>,
+    inode: u64,
+    /// How far into `file` we have read
+    offset: u64,
+    /// Bytes after the last newline we have seen, waiting for the rest of their line
+    partial: Vec<u8>,
+    /// Set when `--from-end` started us partway through a line, whose rest is dropped unparsed
+    discard_line: bool,
+    buffer: Vec<u8>,
+    map: Map,
+}
+
+impl Follower {
+    fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_owned(),
+            file: None,
+            inode: 0,
+            offset: 0,
+            partial: Vec::new(),
+            discard_line: false,
+            buffer: vec![0; CHUNK_SIZE as usize],
+            map: Map::new(),
+        }
+    }
+
+    /// Skip over everything currently in the file, including the rest of a line still being
+    /// written at its end.
+    fn seek_to_end(&mut self) -> Result<()> {
+        self.reopen_if_rotated()?;
+        if let Some(file) = &self.file {
+            let len = file.metadata()?.len();
+            let mut last = [0];
+            if len > 0 {
+                file.read_exact_at(&mut last, len - 1)?;
+            }
+            self.discard_line = len > 0 && last[0] != b'\n';
+            self.offset = len;
+        }
+        Ok(())
+    }
+
+    /// Switch to a new file if the path now points at a different inode.
+    ///
+    /// Anything still left in the old file is read before it is let go, so lines written just
+    /// before a rotation are not lost. If the path doesn't exist right now we keep the old file.
+    fn reopen_if_rotated(&mut self) -> Result<bool> {
+        let metadata = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err.into()),
+        };
+        if self.file.is_some() && metadata.ino() == self.inode {
+            return Ok(false);
+        }
+
+        if self.file.is_some() {
+            self.read_appended()?;
+        }
+        self.file = Some(File::open(&self.path)?);
+        self.inode = metadata.ino();
+        self.offset = 0;
+        self.partial.clear();
+        self.discard_line = false;
+        Ok(true)
+    }
+
+    /// Fold any newly appended complete lines into the map.
+    ///
+    /// Returns whether the map changed.
+    fn poll(&mut self) -> Result<bool> {
+        let mut changed = self.reopen_if_rotated()?;
+
+        let Some(file) = &self.file else {
+            return Ok(changed);
+        };
+        if file.metadata()?.len() < self.offset {
+            eprintln!(
+                "{} was truncated, reading it from the start",
+                self.path.display()
+            );
+            self.offset = 0;
+            self.partial.clear();
+            self.discard_line = false;
+            changed = true;
+        }
+
+        Ok(self.read_appended()? || changed)
+    }
+
+    fn read_appended(&mut self) -> Result<bool> {
+        let Some(file) = &self.file else {
+            return Ok(false);
+        };
+
+        let mut changed = false;
+        loop {
+            let read = file.read_at(&mut self.buffer, self.offset)?;
+            if read == 0 {
+                break;
+            }
+            self.offset += read as u64;
+
+            let Some(last_newline) = self.buffer[..read].iter().rposition(|&b| b == b'\n') else {
+                if !self.discard_line {
+                    self.partial.extend_from_slice(&self.buffer[..read]);
+                }
+                continue;
+            };
+
+            let (mut complete, rest) = self.buffer[..read].split_at(last_newline + 1);
+            if self.discard_line {
+                let first_newline = complete.iter().position(|&b| b == b'\n');
+                complete = &complete[first_newline.expect("complete lines end in a newline") + 1..];
+                self.discard_line = false;
+            }
+            let mut lines = std::mem::take(&mut self.partial);
+            lines.extend_from_slice(complete);
+            for line in lines.split(|&b| b == b'\n').filter(|line| !line.is_empty()) {
+                update_map(&mut self.map, line);
+            }
+            changed = true;
+
+            lines.clear();
+            lines.extend_from_slice(rest);
+            self.partial = lines;
+        }
+
+        Ok(changed)
+    }
+}
+
+/// Fold one line into the map, skipping (and reporting) lines that don't parse.
+///
+/// A follower is long-lived, so one bad line from the writer shouldn't take it down.
+fn update_map(map: &mut Map, line: &[u8]) {
+    let (city, temp) = match parse_record(line) {
+        Ok(record) => record,
+        Err(err) => {
+            eprintln!("skipping line: {err}");
+            return;
+        }
+    };
+    let Ok(city) = std::str::from_utf8(city) else {
+        eprintln!("skipping line: non-utf8 city");
+        return;
+    };
+
+    match map.get_mut(city) {
+        Some(records) => records.update(temp),
+        None => {
+            map.insert(city.to_owned(), Records::from_item(temp));
+        }
+    }
+}
+
+/// Watch `path`'s directory and send a message whenever an event concerns `path`.
+fn spawn_watcher(path: &Path) -> Result<mpsc::Receiver<()>> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path
+        .file_name()
+        .ok_or_else(|| format!("{} does not name a file", path.display()))?
+        .to_owned();
+
+    let mut inotify = Inotify::init()?;
+    inotify.watches().add(
+        dir,
+        WatchMask::MODIFY
+            | WatchMask::CREATE
+            | WatchMask::DELETE
+            | WatchMask::MOVED_FROM
+            | WatchMask::MOVED_TO,
+    )?;
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        loop {
+            let Ok(mut events) = inotify.read_events_blocking(&mut buffer) else {
+                return;
+            };
+            if events.any(|event| event.name == Some(name.as_os_str())) && tx.send(()).is_err() {
+                return;
+            }
+        }
+    });
+
+    Ok(rx)
+}
+
+fn print_snapshot(map: &Map, format: Format) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    if stdout.is_terminal() {
+        // clear the screen and redraw in place
+        write!(stdout, "\x1b[2J\x1b[H")?;
+    }
+    output::write(&mut stdout, &stations_from_map(map), format)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+pub fn follow(path: &Path, interval: Duration, from_end: bool, format: Format) -> Result<()> {
+    let events = spawn_watcher(path)?;
+
+    let mut follower = Follower::new(path);
+    if from_end {
+        follower.seek_to_end()?;
+    }
+    let mut dirty = follower.poll()?;
+    let mut next_print = Instant::now();
+
+    loop {
+        if Instant::now() >= next_print {
+            // also poll on every tick, in case an event was coalesced away
+            dirty |= follower.poll()?;
+            if dirty {
+                print_snapshot(&follower.map, format)?;
+                dirty = false;
+            }
+            next_print = Instant::now() + interval;
+        }
+
+        match events.recv_timeout(next_print.saturating_duration_since(Instant::now())) {
+            Ok(()) => {
+                while events.try_recv().is_ok() {}
+                dirty |= follower.poll()?;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return Err("inotify watcher stopped".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn count(follower: &Follower, city: &str) -> u64 {
+        follower.map.get(city).map_or(0, |records| records.count)
+    }
+
+    #[test]
+    fn partial_lines_and_truncation() {
+        let path = std::env::temp_dir().join(format!("1brc-{}-follow.txt", std::process::id()));
+        std::fs::write(&path, "Hamburg;12.0\nBul").unwrap();
+        let append = |text: &str| {
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&path)
+                .unwrap();
+            file.write_all(text.as_bytes()).unwrap();
+        };
+
+        let mut follower = Follower::new(&path);
+        assert!(follower.poll().unwrap());
+        assert_eq!(count(&follower, "Hamburg"), 1);
+        assert_eq!(follower.partial, b"Bul");
+
+        // nothing new until the partial line's newline arrives
+        append("away");
+        follower.poll().unwrap();
+        assert_eq!(follower.map.len(), 1);
+        append("o;8.9\nHamburg;-3.4\n");
+        assert!(follower.poll().unwrap());
+        assert_eq!(count(&follower, "Bulawayo"), 1);
+        assert_eq!(count(&follower, "Hamburg"), 2);
+        assert!(follower.partial.is_empty());
+        assert!(!follower.poll().unwrap());
+
+        // truncated in place, keeping the inode: read from the start again
+        let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(0).unwrap();
+        drop(file);
+        append("Abha;1.0\nPal");
+        assert!(follower.poll().unwrap());
+        assert_eq!(count(&follower, "Abha"), 1);
+        assert_eq!(follower.partial, b"Pal");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn from_end_drops_the_line_it_starts_in() {
+        let path = std::env::temp_dir().join(format!("1brc-{}-from-end.txt", std::process::id()));
+        std::fs::write(&path, "Abha;1.0\nHam").unwrap();
+        let append = |text: &str| {
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&path)
+                .unwrap();
+            file.write_all(text.as_bytes()).unwrap();
+        };
+
+        let mut follower = Follower::new(&path);
+        follower.seek_to_end().unwrap();
+        append("bu");
+        follower.poll().unwrap();
+        assert!(follower.partial.is_empty());
+        append("rg;5.0\nPalermo;2.5\n");
+        assert!(follower.poll().unwrap());
+        assert_eq!(follower.map.len(), 1);
+        assert_eq!(count(&follower, "Palermo"), 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rotation() {
+        let path = std::env::temp_dir().join(format!("1brc-{}-rotation.txt", std::process::id()));
+        let rotated = path.with_extension("txt.1");
+        std::fs::write(&path, "Abha;1.0\n").unwrap();
+
+        let mut follower = Follower::new(&path);
+        assert!(follower.poll().unwrap());
+        assert_eq!(count(&follower, "Abha"), 1);
+
+        // lines written just before the rotation are still read from the old file
+        let mut old = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        old.write_all(b"Abha;2.0\nBul").unwrap();
+        std::fs::rename(&path, &rotated).unwrap();
+        std::fs::write(&path, "Cairo;3.0\n").unwrap();
+        assert!(follower.poll().unwrap());
+        assert_eq!(count(&follower, "Abha"), 2);
+        assert_eq!(count(&follower, "Cairo"), 1);
+        // the old file's partial line is dropped rather than joined to the new file's first line
+        assert!(follower.partial.is_empty());
+
+        // the new file is followed from here on, and the old one is not
+        old.write_all(b"awayo;4.0\n").unwrap();
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"Cairo;4.0\n")
+            .unwrap();
+        assert!(follower.poll().unwrap());
+        assert_eq!(count(&follower, "Cairo"), 2);
+        assert_eq!(follower.map.len(), 2);
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&rotated).unwrap();
+    }
+}
@@ -0,0 +1,168 @@
+//! Running a solver in a forked child, so the run ends before its file mapping is torn down.
+//!
+//! Unmapping a file of a billion rows takes the kernel a good fraction of a second, all of it after
+//! the results are known. With `--fork` the solver runs in a child process, which sends the results
+//! back through a pipe and only then exits; the parent prints them and exits straight away, without
+//! waiting for the child. The solvers hand their mappings to [`release`], which in the child leaves
+//! them for the kernel to clean up when the child exits.
+//!
+//! Forking only copies the calling thread, so a process with any other threads running could fork
+//! a child holding locks that will never be released. The fork is therefore only made while the
+//! process is still single-threaded, and otherwise the solver runs in-process as usual.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{results::Station, Result};
+
+/// Set in the child forked by [`run`]
+static IN_CHILD: AtomicBool = AtomicBool::new(false);
+
+/// Drop a file mapping, unless this is the child forked by [`run`].
+///
+/// The child leaks it instead, so it is only unmapped when the child exits, after the parent has
+/// its results.
+pub fn release<T>(mapping: T) {
+    if IN_CHILD.load(Ordering::Relaxed) {
+        std::mem::forget(mapping);
+    }
+}
+
+/// Run `solve` in a forked child and return its results.
+///
+/// Falls back to running `solve` in this process where forking isn't supported or isn't safe.
+pub fn run(solve: impl FnOnce() -> Result<Vec<Station>>) -> Result<Vec<Station>> {
+    #[cfg(target_os = "linux")]
+    match linux::threads() {
+        Ok(1) => return linux::run(solve),
+        Ok(_) => eprintln!("other threads are already running, not forking"),
+        Err(err) => eprintln!("can't count this process's threads ({err}), not forking"),
+    }
+    #[cfg(not(target_os = "linux"))]
+    eprintln!("forking is only supported on Linux, not forking");
+
+    solve()
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{
+        fs::File,
+        io::{self, Read, Write},
+        os::fd::FromRawFd,
+        panic::{self, AssertUnwindSafe},
+        sync::atomic::Ordering,
+    };
+
+    use super::IN_CHILD;
+    use crate::{results::Station, Result};
+
+    /// What the child sends back: the stations, or why it couldn't produce them
+    type Outcome = std::result::Result<Vec<Station>, String>;
+
+    /// How many threads this process has.
+    pub fn threads() -> io::Result<usize> {
+        Ok(std::fs::read_dir("/proc/self/task")?.count())
+    }
+
+    pub fn run(solve: impl FnOnce() -> Result<Vec<Station>>) -> Result<Vec<Station>> {
+        let mut fds = [0; 2];
+        // SAFETY: `fds` has room for the two descriptors `pipe2` writes
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        // SAFETY: both descriptors were just opened, and each is owned by exactly one `File`
+        let (mut read, write) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+
+        // SAFETY: the process is single-threaded, so the child gets a consistent copy of it
+        match unsafe { libc::fork() } {
+            -1 => Err(io::Error::last_os_error().into()),
+            0 => {
+                drop(read);
+                child(solve, write)
+            }
+            _ => {
+                // the read only ends once every copy of the write end is closed, the child's included
+                drop(write);
+                let mut sent = Vec::new();
+                read.read_to_end(&mut sent)?;
+                if sent.is_empty() {
+                    return Err("the solver's child process exited without results".into());
+                }
+                decode(&sent)
+            }
+        }
+    }
+
+    /// Write `outcome` for [`decode`] to read back.
+    ///
+    /// serde_json is built with `float_roundtrip`, so every `f64` comes back bit for bit.
+    fn encode(outcome: &Outcome, mut out: impl Write) -> io::Result<()> {
+        serde_json::to_writer(&mut out, outcome)?;
+        out.flush()
+    }
+
+    /// Read back what [`encode`] wrote.
+    fn decode(sent: &[u8]) -> Result<Vec<Station>> {
+        let outcome: Outcome = serde_json::from_slice(sent)?;
+        Ok(outcome?)
+    }
+
+    /// Solve, send the outcome to the parent and exit, leaving the mappings for the kernel.
+    ///
+    /// Never returns: the child must not go on to do the parent's work after the solver.
+    fn child(solve: impl FnOnce() -> Result<Vec<Station>>, mut write: File) -> ! {
+        IN_CHILD.store(true, Ordering::Relaxed);
+
+        // a panic has already been reported by the hook, and leaves the parent with no results
+        let Ok(outcome) = panic::catch_unwind(AssertUnwindSafe(solve)) else {
+            std::process::exit(101);
+        };
+        let outcome: Outcome = outcome.map_err(|err| err.to_string());
+        let sent = encode(&outcome, &mut write);
+        drop(write);
+
+        // let go of stdout and stderr too, or a pipeline reading the parent's output would still
+        // wait for the child to finish tearing down
+        // SAFETY: nothing in the child uses them from here on
+        unsafe {
+            libc::close(libc::STDOUT_FILENO);
+            libc::close(libc::STDERR_FILENO);
+        }
+        std::process::exit(if sent.is_ok() { 0 } else { 1 })
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn results_round_trip() {
+            // the last two are read back one bit off without `float_roundtrip`
+            let stations: Vec<_> = [
+                -99.9,
+                12.3,
+                0.1 + 0.2,
+                1021.6160220994475,
+                115.16039823008849,
+            ]
+            .into_iter()
+            .enumerate()
+            .map(|(count, value)| Station {
+                station: format!("Station {count}, \"quoted\""),
+                min: value,
+                mean: value,
+                max: -value,
+                count: count as u64,
+            })
+            .collect();
+
+            let mut sent = Vec::new();
+            encode(&Ok(stations.clone()), &mut sent).unwrap();
+            assert_eq!(decode(&sent).unwrap(), stations);
+
+            let mut sent = Vec::new();
+            encode(&Err("invalid line".into()), &mut sent).unwrap();
+            assert_eq!(decode(&sent).unwrap_err().to_string(), "invalid line");
+        }
+    }
+}
@@ -1,61 +1,88 @@
+use clap::Parser;
+use output::Format;
+use placement::Placement;
 use polars::prelude::*;
+use results::Station;
+use schedule::Scheduler;
 use std::time::Instant;
 use std::{
     fs::File,
+    ops::Range,
     os::unix::fs::{FileExt, MetadataExt},
-    sync::{
-        atomic::{AtomicU64, Ordering},
-        Arc, Mutex,
-    },
+    path::{Path, PathBuf},
+    sync::Arc,
     thread,
 };
-
+use table::StationTable;
+
+#[cfg(target_os = "linux")]
+mod follow;
+mod fork;
+mod mmap;
+mod output;
+mod placement;
 mod purple_mist;
+mod query;
 mod rangnargrootkeorkamp;
+mod results;
+mod schedule;
+mod serve;
+mod table;
+mod temperature;
 mod thebracket;
+#[cfg(target_os = "linux")]
+mod uring;
 
 /// This code has been sourced from here:
 /// https://github.com/coriolinus/1brc/tree/main
 
-/// Size of chunk that each thread will process at a time
+/// Largest chunk that each thread will process at a time
 const CHUNK_SIZE: u64 = 16 * 1024 * 1024;
 /// How much extra space we back the chunk start up by, to ensure we capture the full initial record
 ///
-/// Must be greater than the longest line in the table
-const CHUNK_EXCESS: u64 = 64;
+/// Must be greater than the longest line: a 100-byte name, `;`, `-99.9` and the newline make 107
+const CHUNK_EXCESS: u64 = 128;
 
 type Result<T, E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
 
+/// Measurements for one station, in integer tenths of a degree
 #[derive(Debug, Clone, Copy)]
 struct Records {
     count: u64,
-    min: f32,
-    max: f32,
-    sum: f32,
+    min: i32,
+    max: i32,
+    sum: i64,
+}
+
+/// No records yet, ready to [`update`](Records::update)
+impl Default for Records {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            min: i32::MAX,
+            max: i32::MIN,
+            sum: 0,
+        }
+    }
 }
 
 impl Records {
-    fn update(&mut self, item: f32) {
+    fn update(&mut self, item: i32) {
         self.count += 1;
         self.min = self.min.min(item);
         self.max = self.max.max(item);
-        self.sum += item;
+        self.sum += item as i64;
     }
 
-    fn from_item(item: f32) -> Self {
+    fn from_item(item: i32) -> Self {
         Self {
             count: 1,
             min: item,
             max: item,
-            sum: item,
+            sum: item as i64,
         }
     }
 
-    fn mean(&self) -> f32 {
-        let mean = self.sum / (self.count as f32);
-        (mean * 10.0).round() / 10.0
-    }
-
     fn merge(self, other: Self) -> Self {
         Self {
             count: self.count + other.count,
@@ -64,44 +91,159 @@ impl Records {
             sum: self.sum + other.sum,
         }
     }
+
+    fn to_station(self, station: String) -> Station {
+        Station::from_tenths(
+            station,
+            self.min.into(),
+            self.sum,
+            self.max.into(),
+            self.count,
+        )
+    }
 }
 
 type Map = std::collections::HashMap<String, Records>;
-// note that we defer parsing the slice into a string until as late as possible, which hopefully
-// minimizes access time
-type BorrowedMap<'a> = std::collections::HashMap<&'a [u8], Records>;
+// note that we defer parsing the name into a string until as late as possible: each worker keys its
+// table by the raw bytes, and names are only checked for utf8 once, after the workers' tables are merged
+type WorkerMap = StationTable<Records>;
+
+/// How the std solver gets each chunk off the disk
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Reader {
+    /// One blocking `pread` per chunk
+    #[default]
+    Pread,
+    /// Keep several chunk reads in flight per thread with io_uring (Linux only)
+    IoUring,
+    /// Map the whole file and parse it in place, prefaulting each chunk before it is parsed
+    Mmap,
+}
 
-/// Get an aligned buffer from the given file.
-///
-/// "Aligned" in this case means that the first byte of the returned buffer is the
-/// first byte of a record, and if `offset != 0` then the previous byte of the source file is `\n`,
-/// and the final byte of the returned buffer is `\n`.
-fn get_aligned_buffer<'a>(file: &File, offset: u64, mut buffer: &'a mut [u8]) -> Result<&'a [u8]> {
-    assert!(
-        offset == 0 || offset > CHUNK_EXCESS,
-        "offset must never be less than chunk excess"
-    );
-    let metadata = file.metadata()?;
-    let file_size = metadata.size();
-    if offset > file_size {
-        return Ok(&[]);
+impl Reader {
+    /// Fall back to `pread` when io_uring was requested but can't be used on this machine.
+    fn resolve(self) -> Self {
+        #[cfg(target_os = "linux")]
+        let available = uring::is_available();
+        #[cfg(not(target_os = "linux"))]
+        let available = false;
+
+        if self == Reader::IoUring && !available {
+            eprintln!("io_uring is unavailable, falling back to pread");
+            return Reader::Pread;
+        }
+        self
     }
+}
 
-    let buffer_size = buffer.len().min((file_size - offset) as usize);
-    buffer = &mut buffer[..buffer_size];
+/// The solvers which can produce a full set of [`Station`] results
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Solver {
+    #[default]
+    Std,
+    Polars,
+    PurpleMist,
+    TheBracket,
+    Ragnar,
+}
 
-    let mut head;
-    let read_from;
+#[derive(Debug, Clone, clap::Args)]
+struct SolverOptions {
+    /// The measurements file to aggregate
+    #[clap(long, global = true, default_value = "../measurements.txt")]
+    input: PathBuf,
 
-    if offset == 0 {
-        head = 0;
-        read_from = 0;
-    } else {
-        head = CHUNK_EXCESS as usize;
-        read_from = offset - CHUNK_EXCESS;
-    };
+    /// How the std solver reads the measurements file
+    #[clap(long, global = true, value_enum, default_value_t)]
+    reader: Reader,
 
-    file.read_exact_at(buffer, read_from)?;
+    /// Memory cap for the purple_mist solver's streaming buffer, in MiB
+    #[clap(long, global = true, default_value_t = 256)]
+    memory_cap: usize,
+
+    /// Worker threads for the std, thebracket and ragnar solvers; defaults to one per core
+    #[clap(long, global = true)]
+    threads: Option<usize>,
+
+    /// Pin each worker thread to its own core, grouped by NUMA node (Linux only)
+    #[clap(long, global = true)]
+    pin: bool,
+}
+
+impl SolverOptions {
+    fn memory_cap_bytes(&self) -> usize {
+        self.memory_cap * 1024 * 1024
+    }
+
+    fn placement(&self) -> Placement {
+        let threads = schedule::threads(self.threads);
+        if self.pin {
+            Placement::pinned(threads)
+        } else {
+            Placement::unpinned(threads)
+        }
+    }
+}
+
+fn solve(solver: Solver, options: &SolverOptions) -> Result<Vec<Station>> {
+    let path = options.input.as_path();
+    let placement = options.placement();
+    Ok(match solver {
+        Solver::Std => solve_std(path, options.reader, &placement)?,
+        Solver::Polars => solve_polars(path)?,
+        Solver::PurpleMist => purple_mist::solve(path, options.memory_cap_bytes())?,
+        Solver::TheBracket => thebracket::solve(path, &placement)?,
+        Solver::Ragnar => rangnargrootkeorkamp::solve(path, &placement),
+    })
+}
+
+#[derive(Debug, clap::Parser)]
+struct Args {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    #[clap(flatten)]
+    options: SolverOptions,
+
+    /// Only run this solver, instead of timing all of them one after another
+    #[clap(long, value_enum)]
+    solver: Option<Solver>,
+
+    /// How results are written to stdout
+    #[clap(long, global = true, value_enum, default_value_t)]
+    format: Format,
+
+    /// Write the results to this file instead of stdout, in the format given by its extension
+    /// (`.parquet`, `.arrow`, `.json`, `.csv` or `.txt`)
+    #[clap(long, requires = "solver")]
+    output: Option<PathBuf>,
+
+    /// Solve in a forked child and exit as soon as it sends the results, leaving it to unmap the
+    /// file in the background (Linux only)
+    #[clap(long, requires = "solver")]
+    fork: bool,
+}
+
+/// Work out which part of the file must be read for `chunk`.
+///
+/// Returns `(read_from, len)`: the window starts [`CHUNK_EXCESS`] bytes early (unless the chunk
+/// starts the file) so that the first record can be recovered.
+fn chunk_window(chunk: &Range<u64>) -> (u64, usize) {
+    let read_from = chunk.start.saturating_sub(CHUNK_EXCESS);
+    (read_from, (chunk.end - read_from) as usize)
+}
+
+/// Trim a buffer read from [`chunk_window`] down to the records owned by its chunk, which starts
+/// `head` bytes into the buffer.
+///
+/// "Aligned" in this case means that the first byte of the returned buffer is the
+/// first byte of a record, and unless the chunk starts the file the previous byte of the source
+/// file is `\n`, and the final byte of the returned buffer is `\n`. The exception is a chunk that
+/// `ends_file`, which also keeps a final line without a newline, as [`schedule::align`] does.
+fn align_chunk(mut head: usize, buffer: &[u8], ends_file: bool) -> &[u8] {
+    if buffer.is_empty() {
+        return buffer;
+    }
 
     // step backwards until we find the end of the previous record
     // then drop all elements before that
@@ -113,165 +255,419 @@ fn get_aligned_buffer<'a>(file: &File, offset: u64, mut buffer: &'a mut [u8]) ->
     }
 
     // find the end of the final valid record
-    let mut tail = buffer.len() - 1;
-    while buffer[tail] != b'\n' {
-        tail -= 1;
-    }
+    let tail = if ends_file {
+        buffer.len()
+    } else {
+        match buffer.iter().rposition(|&b| b == b'\n') {
+            Some(newline) => newline + 1,
+            None => return &[],
+        }
+    };
 
-    Ok(&buffer[head..=tail])
+    &buffer[head..tail.max(head)]
+}
+
+/// Get an aligned buffer for `chunk` from the given file.
+///
+/// See [`align_chunk`] for what "aligned" means here.
+fn get_aligned_buffer<'a>(
+    file: &File,
+    chunk: &Range<u64>,
+    ends_file: bool,
+    buffer: &'a mut [u8],
+) -> Result<&'a [u8]> {
+    let (read_from, len) = chunk_window(chunk);
+    let buffer = &mut buffer[..len];
+    file.read_exact_at(buffer, read_from)?;
+
+    Ok(align_chunk(
+        (chunk.start - read_from) as usize,
+        buffer,
+        ends_file,
+    ))
 }
 
 fn process_chunk(
     file: &File,
-    offset: u64,
-    outer_map: &mut Arc<Mutex<Map>>,
+    chunk: &Range<u64>,
+    ends_file: bool,
+    map: &mut WorkerMap,
     buffer: &mut [u8],
 ) -> Result<()> {
-    let aligned_buffer = get_aligned_buffer(file, offset, buffer)?;
-    let mut map = BorrowedMap::new();
+    let aligned_buffer = get_aligned_buffer(file, chunk, ends_file, buffer)?;
+    aggregate_chunk(aligned_buffer, map)
+}
 
+/// Split a single `city;temp` record (without its trailing newline), with the temperature in
+/// tenths of a degree.
+fn parse_record(line: &[u8]) -> Result<(&[u8], i32)> {
+    let split_point = line
+        .iter()
+        .enumerate()
+        .find_map(|(idx, &b)| (b == b';').then_some(idx))
+        .ok_or_else(|| {
+            let line = std::str::from_utf8(line).unwrap_or("<invalid utf8>");
+            format!("no ';' in {line}")
+        })?;
+
+    let temp = &line[split_point + 1..];
+    let temp = temperature::parse_exact(temp).ok_or_else(|| {
+        let temp = String::from_utf8_lossy(temp);
+        format!("parsing temperature {temp:?}")
+    })?;
+
+    Ok((&line[..split_point], temp))
+}
+
+/// Parse every record of an aligned chunk and fold them into the worker's `map`.
+fn aggregate_chunk(aligned_buffer: &[u8], map: &mut WorkerMap) -> Result<()> {
     for line in aligned_buffer
         .split(|&b| b == b'\n')
         .filter(|line| !line.is_empty())
     {
-        let split_point = line
-            .iter()
-            .enumerate()
-            .find_map(|(idx, &b)| (b == b';').then_some(idx))
-            .ok_or_else(|| {
-                let line = std::str::from_utf8(line).unwrap_or("<invalid utf8>");
-                format!("no ';' in {line}")
-            })?;
-
-        let temp = std::str::from_utf8(&line[split_point + 1..])
-            .map_err(|err| format!("non-utf8 temp: {err}"))?;
-        let temp: f32 = temp
-            .parse()
-            .map_err(|err| format!("parsing {temp}: {err}"))?;
-
-        let city = &line[..split_point];
-
-        map.entry(city)
-            .and_modify(|records| records.update(temp))
-            .or_insert_with(|| Records::from_item(temp));
-    }
-
-    // that should have taken a while; long enough that we can now cheaply update the outer map
-    // without worrying too much about contention from other threads
-    let mut outer = outer_map.lock().expect("non-poisoned mutex");
-    for (city, records) in map.into_iter() {
-        let city =
-            String::from_utf8(city.to_owned()).map_err(|err| format!("non-utf8 city: {err}"))?;
-        outer
-            .entry(city)
-            .and_modify(|outer_records| *outer_records = outer_records.merge(records))
-            .or_insert(records);
+        let (city, temp) = parse_record(line)?;
+        map.get_or_default(city).update(temp);
     }
 
     Ok(())
 }
 
-fn distribute_work(file: &File) -> Result<Map> {
+/// Fold one worker's map into another's.
+fn merge_worker_maps(into: &mut WorkerMap, from: WorkerMap) {
+    into.merge(from, |into_records, records| {
+        *into_records = into_records.merge(records)
+    });
+}
+
+fn distribute_work(file: &File, reader: Reader, placement: &Placement) -> Result<Map> {
     let metadata = file.metadata()?;
     let file_size = metadata.size();
 
-    let offset = Arc::new(AtomicU64::new(0));
-    let map = Arc::new(Mutex::new(Map::new()));
-
-    thread::scope(|scope| {
-        for _ in 0..thread::available_parallelism().map(Into::into).unwrap_or(1) {
-            let offset = offset.clone();
-            let mut map = map.clone();
-            scope.spawn(move || {
-                let mut buffer = vec![0; (CHUNK_SIZE + CHUNK_EXCESS) as usize];
-                loop {
-                    let offset = offset.fetch_add(CHUNK_SIZE, Ordering::SeqCst);
-                    if offset > file_size {
-                        break;
-                    }
+    let reader = reader.resolve();
+    let scheduler = Scheduler::new(file_size, placement.nodes(), CHUNK_SIZE);
+    let mapped = match reader {
+        Reader::Mmap => Some(mmap::map(file)?),
+        _ => None,
+    };
 
-                    process_chunk(file, offset, &mut map, &mut buffer)
-                        .expect("processing a chunk should always succeed");
-                }
-            });
-        }
-    });
+    // every worker keeps its own map for the whole run, so there is no locking until they're merged
+    let maps = thread::scope(|scope| {
+        let handles = (0..scheduler.workers())
+            .map(|worker| {
+                let scheduler = &scheduler;
+                let mapped = mapped.as_ref();
+                // errors are sent back as strings, since the boxed ones can't cross threads
+                scope.spawn(move || -> Result<WorkerMap, String> {
+                    placement.pin(worker);
+                    let mut map = WorkerMap::new();
+
+                    if let Some(mapped) = mapped {
+                        mmap::process_chunks(mapped, scheduler, worker, &mut map)
+                            .map_err(|err| err.to_string())?;
+                        return Ok(map);
+                    }
 
-    Ok(Arc::into_inner(map)
-        .expect("all other references to map have gone out of scope")
-        .into_inner()
-        .expect("no poisoned mutexes in this program"))
-}
+                    #[cfg(target_os = "linux")]
+                    if reader == Reader::IoUring {
+                        uring::process_chunks(file, scheduler, worker, &mut map)
+                            .map_err(|err| err.to_string())?;
+                        return Ok(map);
+                    }
 
-fn run_std() -> Result<()> {
-    let file = std::fs::File::open("../measurements.txt")?;
-    let map = distribute_work(&file)?;
+                    let mut buffer = vec![0; (CHUNK_SIZE + CHUNK_EXCESS) as usize];
+                    while let Some(chunk) = scheduler.next(worker) {
+                        let ends_file = scheduler.ends_file(&chunk);
+                        process_chunk(file, &chunk, ends_file, &mut map, &mut buffer)
+                            .map_err(|err| err.to_string())?;
+                    }
+                    Ok(map)
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|_| Err("a worker panicked".to_owned()))
+            })
+            .collect::<Result<Vec<_>, _>>()
+    })?;
+    if let Some(mapped) = mapped {
+        fork::release(mapped);
+    }
 
-    let mut keys = map.keys().collect::<Vec<_>>();
-    keys.sort_unstable();
+    schedule::reduce(maps, merge_worker_maps)
+        .unwrap_or_default()
+        .into_entries()
+        .map(|(city, records)| {
+            let city = String::from_utf8(city).map_err(|err| format!("non-utf8 city: {err}"))?;
+            Ok((city, records))
+        })
+        .collect()
+}
 
-    for key in keys {
-        let record = map[key];
-        let min = record.min;
-        let mean = record.mean();
-        let max = record.max;
+fn stations_from_map(map: &Map) -> Vec<Station> {
+    let mut stations = map
+        .iter()
+        .map(|(city, records)| records.to_station(city.clone()))
+        .collect::<Vec<_>>();
+    results::sort(&mut stations);
+    stations
+}
 
-        println!("{key}: {min}/{mean}/{max}");
-    }
+fn solve_std(path: &Path, reader: Reader, placement: &Placement) -> Result<Vec<Station>> {
+    let file = std::fs::File::open(path)?;
+    let map = distribute_work(&file, reader, placement)?;
 
-    Ok(())
+    Ok(stations_from_map(&map))
 }
 
-fn run_polars() -> Result<DataFrame, PolarsError> {
-    let now = Instant::now();
-
+/// Lazily scan the measurements file as a `station`/`measure` frame.
+fn scan_measurements(path: &Path) -> PolarsResult<LazyFrame> {
     let f1: Field = Field::new("station", DataType::String);
     let f2: Field = Field::new("measure", DataType::Float64);
     let sc: Schema = Schema::from_iter(vec![f1, f2]);
 
-    let q = LazyCsvReader::new("../measurements.txt")
+    LazyCsvReader::new(path)
         .has_header(false)
         .with_schema(Some(Arc::new(sc)))
         .with_separator(b';')
-        .finish()?
-        .group_by(vec![col("station")])
-        .agg(vec![
-            col("measure").alias("min").min(),
-            col("measure").alias("mean").mean(),
-            col("measure").alias("max").max(),
+        .finish()
+}
+
+/// Aggregate with polars' streaming engine.
+///
+/// Temperatures are converted to integer tenths before aggregating, like the hand-written solvers
+/// do, so the sum (and therefore the mean) is exact rather than accumulated in floating point.
+fn solve_polars(path: &Path) -> Result<Vec<Station>> {
+    let df = scan_measurements(path)?
+        .select([
+            col("station"),
+            (col("measure") * lit(10))
+                .round(0)
+                .cast(DataType::Int64)
+                .alias("tenths"),
         ])
-        .sort("station", Default::default())
-        .with_streaming(true);
+        .group_by([col("station")])
+        .agg([
+            col("tenths").min().alias("min"),
+            col("tenths").sum().alias("sum"),
+            col("tenths").max().alias("max"),
+            col("tenths").count().alias("count"),
+        ])
+        .with_streaming(true)
+        .collect()?;
+
+    let names = df.column("station")?.str()?;
+    let min = df.column("min")?.i64()?;
+    let sum = df.column("sum")?.i64()?;
+    let max = df.column("max")?.i64()?;
+    let count = df.column("count")?.cast(&DataType::UInt64)?;
+    let count = count.u64()?;
+
+    let mut stations = names
+        .into_iter()
+        .zip(min)
+        .zip(sum)
+        .zip(max)
+        .zip(count)
+        .map(|((((name, min), sum), max), count)| {
+            let name = name.ok_or("null station name")?;
+            match (min, sum, max, count) {
+                (Some(min), Some(sum), Some(max), Some(count)) => {
+                    Ok(Station::from_tenths(name.to_owned(), min, sum, max, count))
+                }
+                _ => Err(format!("null aggregate for {name}").into()),
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+    results::sort(&mut stations);
 
-    let df = q.collect()?;
+    Ok(stations)
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    /// Tail a file that is still being appended to, printing rolling results
+    #[cfg(target_os = "linux")]
+    Follow {
+        file: std::path::PathBuf,
 
-    println!("Time={} μs", now.elapsed().as_secs());
+        /// Seconds between refreshes of the printed results
+        #[clap(long, default_value_t = 1)]
+        interval: u64,
 
-    Ok(df)
+        /// Skip whatever the file already holds and only aggregate lines appended from now on
+        #[clap(long)]
+        from_end: bool,
+    },
+    /// Serve the aggregated results over HTTP on localhost
+    Serve {
+        /// Which solver computes (and recomputes, on `POST /reload`) the results
+        #[clap(long, value_enum, default_value_t)]
+        solver: Solver,
+
+        #[clap(long, default_value_t = 8080)]
+        port: u16,
+    },
+    /// Run a SQL query over the measurements, which are registered as the `measurements` table
+    /// with `station` and `measure` columns
+    Query {
+        sql: String,
+
+        /// Write the result to this file instead of stdout, in the format given by its extension
+        /// (`.parquet`, `.arrow`, `.json`, `.csv` or `.txt`)
+        #[clap(long)]
+        output: Option<PathBuf>,
+    },
 }
 
 fn main() -> Result<()> {
-    // time
-    let now = Instant::now();
-    run_std()?;
-    println!("Time Rust STD={} seconds", now.elapsed().as_secs());
+    // polars elides the middle of long frames unless told otherwise. It reads this for every frame
+    // it prints, so it is set here, before any thread that might be reading the environment starts
+    if std::env::var_os("POLARS_FMT_MAX_ROWS").is_none() {
+        std::env::set_var("POLARS_FMT_MAX_ROWS", "-1");
+    }
+    let args = Args::parse();
+
+    if let Some(command) = args.command {
+        return match command {
+            #[cfg(target_os = "linux")]
+            Command::Follow {
+                file,
+                interval,
+                from_end,
+            } => follow::follow(
+                &file,
+                std::time::Duration::from_secs(interval),
+                from_end,
+                args.format,
+            ),
+            Command::Serve { solver, port } => serve::serve(port, solver, &args.options),
+            Command::Query { sql, output } => {
+                query::query(&sql, &args.options.input, args.format, output.as_deref())
+            }
+        };
+    }
+    let options = args.options;
+    if options.pin {
+        eprintln!("Placement: {}", options.placement());
+    }
 
-    // let now = Instant::now();
-    // let df = run_polars()?;
-    // println!("Time Polars={} seconds", now.elapsed().as_secs());
-    // println!("{:?}", df);
+    if let Some(solver) = args.solver {
+        let stations = if args.fork {
+            fork::run(|| solve(solver, &options))?
+        } else {
+            solve(solver, &options)?
+        };
+        match args.output {
+            Some(path) => output::write_file(&path, &stations)?,
+            None => output::print(&stations, args.format)?,
+        }
+        return Ok(());
+    }
 
-    // Crashes Due to much RAM usage
-    // purple_mist::main()?;
+    for solver in [
+        Solver::Std,
+        Solver::Polars,
+        Solver::PurpleMist,
+        Solver::Ragnar,
+        Solver::TheBracket,
+    ] {
+        let now = Instant::now();
+        let stations = solve(solver, &options)?;
+        output::print(&stations, args.format)?;
+        eprintln!("Time {solver:?}={:.2} seconds", now.elapsed().as_secs_f32());
+    }
 
-    rangnargrootkeorkamp::main();
+    Ok(())
+}
 
-    let now = Instant::now();
-    thebracket::read_file()?;
-    println!(
-        "Time Rust The Bracker Implementation={} seconds",
-        now.elapsed().as_secs()
-    );
+#[cfg(test)]
+mod test {
+    use clap::ValueEnum;
+
+    use super::*;
+
+    /// Write a deterministic file covering negative values, `x.0` values and a name with a comma.
+    fn write_fixture(name: &str) -> PathBuf {
+        let names = ["Abha", "Zürich", "Washington, D.C.", "Xi'an", "A"];
+        let mut contents = String::new();
+        let mut state = 12345_u64;
+        for _ in 0..50_000 {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let station = names[(state >> 33) as usize % names.len()];
+            let tenths = ((state >> 40) % 1999) as i64 - 999;
+            let sign = if tenths < 0 { "-" } else { "" };
+            contents += &format!(
+                "{station};{sign}{}.{}\n",
+                tenths.abs() / 10,
+                tenths.abs() % 10
+            );
+        }
 
-    Ok(())
+        let path = std::env::temp_dir().join(format!("1brc-{}-{name}.txt", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Every solver must agree with the std solver on the same input.
+    ///
+    /// The std solver is run with every reader, and ragnar's solver only on CPUs with the AVX2 and
+    /// BMI2 it needs. Each also reads a copy of the file without the last newline, which must give
+    /// the same results. Under test, [`schedule::MIN_CHUNK`] is small enough that the file is split
+    /// into dozens of chunks per worker.
+    #[test]
+    fn solvers_agree() {
+        let options = SolverOptions {
+            input: write_fixture("solvers_agree"),
+            reader: Reader::Pread,
+            memory_cap: 1,
+            threads: Some(3),
+            pin: false,
+        };
+        let unterminated = options.input.with_extension("unterminated.txt");
+        let mut contents = std::fs::read(&options.input).unwrap();
+        assert_eq!(contents.pop(), Some(b'\n'));
+        std::fs::write(&unterminated, contents).unwrap();
+
+        let expected = solve(Solver::Std, &options).unwrap();
+        let mut runs: Vec<_> = Reader::value_variants()
+            .iter()
+            .map(|&reader| (Solver::Std, reader))
+            .collect();
+        let mut solvers = vec![Solver::Polars, Solver::PurpleMist, Solver::TheBracket];
+        #[cfg(target_arch = "x86_64")]
+        if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("bmi2") {
+            solvers.push(Solver::Ragnar);
+        }
+        runs.extend(solvers.into_iter().map(|solver| (solver, Reader::Pread)));
+
+        for input in [&options.input, &unterminated] {
+            for &(solver, reader) in &runs {
+                let options = SolverOptions {
+                    input: input.clone(),
+                    reader,
+                    ..options.clone()
+                };
+                let stations = solve(solver, &options).unwrap();
+                let run = format!("{solver:?} {reader:?} {}", input.display());
+                assert_eq!(stations.len(), expected.len(), "{run}");
+                for (station, expected) in stations.iter().zip(&expected) {
+                    let run = format!("{run} {}", station.station);
+                    assert_eq!(station.station, expected.station, "{run}");
+                    assert_eq!(station.count, expected.count, "{run}");
+                    assert_eq!(station.min, expected.min, "{run}");
+                    assert_eq!(station.max, expected.max, "{run}");
+                    assert_eq!(station.mean, expected.mean, "{run}");
+                }
+            }
+        }
+
+        std::fs::remove_file(&options.input).unwrap();
+        std::fs::remove_file(&unterminated).unwrap();
+    }
 }
@@ -0,0 +1,73 @@
+//! An mmap backed reader for the chunked std solver.
+//!
+//! The pread path copies every chunk out of the page cache into a per-thread buffer. Here the whole
+//! file is mapped once and each worker parses its chunks in place. Left to itself, the kernel would
+//! fault the mapping in one page at a time as the parser walks it, so the mapping is marked
+//! sequential (and eligible for huge pages where the kernel supports it), and each worker prefaults
+//! a chunk as it claims it. That way the page faults for a chunk are taken in one go, on the core
+//! that is about to read it.
+
+use std::fs::File;
+
+use memmap2::{Advice, Mmap};
+
+use crate::{aggregate_chunk, align_chunk, chunk_window, schedule::Scheduler, Result, WorkerMap};
+
+/// Bytes between the reads that prefault a chunk where `MADV_POPULATE_READ` is unsupported
+const PAGE_SIZE: usize = 4096;
+
+/// Map all of `file` read-only, with advice for reading it front to back once.
+pub fn map(file: &File) -> Result<Mmap> {
+    // SAFETY: the measurements file must not be truncated while we read it, as for the other
+    // mmap based solvers
+    let mmap = unsafe { Mmap::map(file)? };
+    if mmap.is_empty() {
+        return Ok(mmap);
+    }
+
+    mmap.advise(Advice::Sequential)?;
+    // huge pages for file mappings depend on the kernel's configuration, so this is only a hint
+    #[cfg(target_os = "linux")]
+    let _ = mmap.advise(Advice::HugePage);
+
+    Ok(mmap)
+}
+
+/// Fault in `len` bytes of `mmap` from `offset`, so parsing them doesn't stop at every page.
+fn prefault(mmap: &Mmap, offset: usize, len: usize) -> Result<()> {
+    mmap.advise_range(Advice::WillNeed, offset, len)?;
+
+    // MADV_POPULATE_READ needs Linux 5.14; before that, touch one byte of every page instead
+    #[cfg(target_os = "linux")]
+    if mmap.advise_range(Advice::PopulateRead, offset, len).is_ok() {
+        return Ok(());
+    }
+    for page in mmap[offset..offset + len].iter().step_by(PAGE_SIZE) {
+        // SAFETY: `page` is a valid reference; the volatile read stops it being optimised out
+        unsafe { std::ptr::read_volatile(page) };
+    }
+    Ok(())
+}
+
+/// Worker loop: process chunks until the scheduler has handed out the whole file.
+pub fn process_chunks(
+    mmap: &Mmap,
+    scheduler: &Scheduler,
+    worker: usize,
+    map: &mut WorkerMap,
+) -> Result<()> {
+    while let Some(chunk) = scheduler.next(worker) {
+        let (read_from, len) = chunk_window(&chunk);
+        let read_from = read_from as usize;
+        prefault(mmap, read_from, len)?;
+
+        let window = &mmap[read_from..read_from + len];
+        let ends_file = scheduler.ends_file(&chunk);
+        aggregate_chunk(
+            align_chunk(chunk.start as usize - read_from, window, ends_file),
+            map,
+        )?;
+    }
+
+    Ok(())
+}
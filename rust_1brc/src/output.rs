@@ -0,0 +1,269 @@
+//! Writing [`Station`] results in the formats selected with `--format` or `--output`.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use polars::prelude::{
+    CsvWriter, DataFrame, IpcWriter, JsonFormat, JsonWriter, NamedFrom, ParquetWriter,
+    PolarsResult, SerWriter, Series,
+};
+
+use crate::results::Station;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// The challenge's own `{Abha=-23.0/18.0/59.2, ...}` line
+    #[default]
+    Text,
+    /// An array of `{"station", "min", "mean", "max", "count"}` objects
+    Json,
+    /// RFC 4180 CSV with a `station,min,mean,max,count` header
+    Csv,
+}
+
+pub fn write(out: &mut impl Write, stations: &[Station], format: Format) -> io::Result<()> {
+    match format {
+        Format::Text => write_text(out, stations),
+        Format::Json => {
+            serde_json::to_writer(&mut *out, stations)?;
+            writeln!(out)
+        }
+        Format::Csv => write_csv(out, stations),
+    }
+}
+
+/// Write to stdout, for the common case of printing a final result.
+pub fn print(stations: &[Station], format: Format) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    write(&mut stdout, stations, format)?;
+    stdout.flush()
+}
+
+fn write_text(out: &mut impl Write, stations: &[Station]) -> io::Result<()> {
+    write!(out, "{{")?;
+    for (idx, station) in stations.iter().enumerate() {
+        if idx > 0 {
+            write!(out, ", ")?;
+        }
+        write!(
+            out,
+            "{}={:.1}/{:.1}/{:.1}",
+            station.station, station.min, station.mean, station.max
+        )?;
+    }
+    writeln!(out, "}}")
+}
+
+/// Quote a CSV field if it contains a separator, a quote or a line break.
+fn csv_field(field: &str) -> std::borrow::Cow<'_, str> {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\"")).into()
+    } else {
+        field.into()
+    }
+}
+
+fn write_csv(out: &mut impl Write, stations: &[Station]) -> io::Result<()> {
+    write!(out, "station,min,mean,max,count\r\n")?;
+    for station in stations {
+        write!(
+            out,
+            "{},{:.1},{:.1},{:.1},{}\r\n",
+            csv_field(&station.station),
+            station.min,
+            station.mean,
+            station.max,
+            station.count
+        )?;
+    }
+    Ok(())
+}
+
+/// Build a polars frame with `station`, `min`, `mean`, `max` and `count` columns.
+pub fn to_dataframe(stations: &[Station]) -> PolarsResult<DataFrame> {
+    DataFrame::new(vec![
+        Series::new(
+            "station",
+            stations
+                .iter()
+                .map(|station| station.station.as_str())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "min",
+            stations
+                .iter()
+                .map(|station| station.min)
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "mean",
+            stations
+                .iter()
+                .map(|station| station.mean)
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "max",
+            stations
+                .iter()
+                .map(|station| station.max)
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "count",
+            stations
+                .iter()
+                .map(|station| station.count)
+                .collect::<Vec<_>>(),
+        ),
+    ])
+}
+
+/// Write an arbitrary frame, such as the result of a `query`, in one of the stdout formats.
+pub fn write_dataframe(
+    out: &mut impl Write,
+    df: &mut DataFrame,
+    format: Format,
+) -> crate::Result<()> {
+    match format {
+        // `main` lifts polars' row limit, so the whole frame is printed
+        Format::Text => writeln!(out, "{df}")?,
+        Format::Json => JsonWriter::new(out)
+            .with_json_format(JsonFormat::Json)
+            .finish(df)?,
+        Format::Csv => CsvWriter::new(out).finish(df)?,
+    }
+    Ok(())
+}
+
+/// What `--output` writes, going by the file extension
+enum FileFormat {
+    Parquet,
+    Arrow,
+    Plain(Format),
+}
+
+fn file_format(path: &Path) -> crate::Result<FileFormat> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default();
+    Ok(match extension {
+        "parquet" => FileFormat::Parquet,
+        "arrow" | "ipc" | "feather" => FileFormat::Arrow,
+        "json" => FileFormat::Plain(Format::Json),
+        "csv" => FileFormat::Plain(Format::Csv),
+        "txt" => FileFormat::Plain(Format::Text),
+        _ => {
+            return Err(format!(
+                "cannot tell what format to write {} in: expected .parquet, .arrow, .json, .csv or .txt",
+                path.display()
+            )
+            .into())
+        }
+    })
+}
+
+/// Write results to `path`, choosing the format from its extension.
+///
+/// `.parquet` and `.arrow` (Arrow IPC) files go through [`to_dataframe`] and the polars writers;
+/// `.json`, `.csv` and `.txt` use the same writers as stdout.
+pub fn write_file(path: &Path, stations: &[Station]) -> crate::Result<()> {
+    match file_format(path)? {
+        FileFormat::Parquet | FileFormat::Arrow => {
+            write_dataframe_file(path, &mut to_dataframe(stations)?)
+        }
+        FileFormat::Plain(format) => {
+            let mut out = BufWriter::new(File::create(path)?);
+            write(&mut out, stations, format)?;
+            out.flush()?;
+            Ok(())
+        }
+    }
+}
+
+/// Write a frame to `path`, choosing the format from its extension.
+pub fn write_dataframe_file(path: &Path, df: &mut DataFrame) -> crate::Result<()> {
+    let format = file_format(path)?;
+    let mut out = BufWriter::new(File::create(path)?);
+    match format {
+        FileFormat::Parquet => {
+            ParquetWriter::new(&mut out).finish(df)?;
+        }
+        FileFormat::Arrow => IpcWriter::new(&mut out).finish(df)?,
+        FileFormat::Plain(format) => write_dataframe(&mut out, df, format)?,
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn csv_quoting() {
+        assert_eq!(csv_field("Abha"), "Abha");
+        assert_eq!(csv_field("Washington, D.C."), "\"Washington, D.C.\"");
+        assert_eq!(csv_field("The \"Big\" Apple"), "\"The \"\"Big\"\" Apple\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn csv_format() {
+        let stations = [
+            Station::from_tenths("Abha".into(), 120, 360, 240, 2),
+            Station::from_tenths("Washington, D.C.".into(), -5, -5, -5, 1),
+        ];
+        let mut out = Vec::new();
+        write(&mut out, &stations, Format::Csv).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "station,min,mean,max,count\r\nAbha,12.0,18.0,24.0,2\r\n\"Washington, D.C.\",-0.5,-0.5,-0.5,1\r\n"
+        );
+    }
+
+    #[test]
+    fn dataframe_files_round_trip() {
+        use polars::prelude::{IpcReader, ParquetReader, SerReader};
+
+        let stations = [
+            Station::from_tenths("Abha".into(), -230, 360, 592, 2),
+            Station::from_tenths("Zürich".into(), -5, -5, -5, 1),
+        ];
+        let expected = to_dataframe(&stations).unwrap();
+        for extension in ["parquet", "arrow"] {
+            let path = std::env::temp_dir().join(format!(
+                "1brc-{}-round-trip.{extension}",
+                std::process::id()
+            ));
+            write_file(&path, &stations).unwrap();
+            let file = File::open(&path).unwrap();
+            let df = match extension {
+                "parquet" => ParquetReader::new(file).finish(),
+                _ => IpcReader::new(file).finish(),
+            }
+            .unwrap();
+            assert!(df.equals(&expected), "{extension}: {df}");
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn text_format() {
+        let stations = [
+            Station::from_tenths("Abha".into(), -230, 360, 592, 2),
+            Station::from_tenths("Zürich".into(), -5, -5, -5, 1),
+        ];
+        let mut out = Vec::new();
+        write(&mut out, &stations, Format::Text).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{Abha=-23.0/18.0/59.2, Zürich=-0.5/-0.5/-0.5}\n"
+        );
+    }
+}
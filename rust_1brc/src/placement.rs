@@ -0,0 +1,268 @@
+//! Which core, and which NUMA node, each worker thread runs on.
+//!
+//! Left alone, the scheduler is free to move workers between sockets, so a worker can end up
+//! reading pages that live on the other node. With `--pin` every worker is pinned to one core with
+//! `sched_setaffinity`. The cores are taken node by node from the topology in `/sys`, so the
+//! workers on a node are numbered consecutively and [`Scheduler`](crate::schedule::Scheduler) can
+//! hand each node one contiguous part of the file.
+
+use std::fmt;
+
+#[derive(Debug, Clone)]
+pub struct Placement {
+    /// The core each worker is pinned to, or `None` when the OS is left to place them
+    cpus: Option<Vec<usize>>,
+    /// The NUMA node each worker is on
+    nodes: Vec<usize>,
+}
+
+impl Placement {
+    /// `threads` workers that run wherever the OS puts them.
+    pub fn unpinned(threads: usize) -> Self {
+        Self {
+            cpus: None,
+            nodes: vec![0; threads.max(1)],
+        }
+    }
+
+    /// `threads` workers spread evenly over the cores this process may run on, node by node.
+    ///
+    /// Falls back to [`Placement::unpinned`] where pinning isn't supported.
+    pub fn pinned(threads: usize) -> Self {
+        #[cfg(target_os = "linux")]
+        match linux::topology() {
+            Ok(topology) => return Self::spread(threads, &topology),
+            Err(err) => eprintln!("can't read the CPU topology ({err}), not pinning"),
+        }
+        #[cfg(not(target_os = "linux"))]
+        eprintln!("pinning is only supported on Linux, not pinning");
+
+        Self::unpinned(threads)
+    }
+
+    /// Spread `threads` workers over `topology`, a list of the usable cores on each node.
+    ///
+    /// Every node gets a share of the workers in proportion to its cores. If there are more
+    /// workers than cores, some cores get more than one.
+    fn spread(threads: usize, topology: &[(usize, Vec<usize>)]) -> Self {
+        let cores = topology
+            .iter()
+            .flat_map(|(node, cpus)| cpus.iter().map(move |&cpu| (*node, cpu)))
+            .collect::<Vec<_>>();
+        if cores.is_empty() {
+            return Self::unpinned(threads);
+        }
+
+        let threads = threads.max(1);
+        let (nodes, cpus) = (0..threads)
+            .map(|worker| cores[worker * cores.len() / threads])
+            .unzip();
+        Self {
+            cpus: Some(cpus),
+            nodes,
+        }
+    }
+
+    pub fn workers(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// The NUMA node of every worker, in worker order.
+    pub fn nodes(&self) -> &[usize] {
+        &self.nodes
+    }
+
+    /// Pin the calling thread to `worker`'s core, if there is one.
+    ///
+    /// A worker that can't be pinned still does its share, just wherever the OS runs it.
+    pub fn pin(&self, worker: usize) {
+        let Some(cpus) = &self.cpus else {
+            return;
+        };
+        #[cfg(target_os = "linux")]
+        if let Err(err) = linux::pin_current_thread(cpus[worker]) {
+            eprintln!("pinning worker {worker} to cpu {}: {err}", cpus[worker]);
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = (cpus, worker);
+    }
+}
+
+/// `0-3,8` style, like the kernel's own cpu lists
+fn format_list(list: &[usize]) -> String {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &item in list {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == item => *end = item,
+            _ => ranges.push((item, item)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|&(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{start}-{end}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl fmt::Display for Placement {
+    /// e.g. `node 0: workers 0-3 on cpus 0-3; node 1: workers 4-7 on cpus 16-19`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(cpus) = &self.cpus else {
+            return write!(f, "{} unpinned workers", self.workers());
+        };
+
+        let mut nodes = self.nodes.clone();
+        nodes.dedup();
+        for (idx, node) in nodes.into_iter().enumerate() {
+            let workers = (0..self.workers())
+                .filter(|&worker| self.nodes[worker] == node)
+                .collect::<Vec<_>>();
+            let mut node_cpus = workers
+                .iter()
+                .map(|&worker| cpus[worker])
+                .collect::<Vec<_>>();
+            node_cpus.dedup();
+            if idx > 0 {
+                write!(f, "; ")?;
+            }
+            write!(
+                f,
+                "node {node}: workers {} on cpus {}",
+                format_list(&workers),
+                format_list(&node_cpus)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{fs, io, mem};
+
+    const NODES: &str = "/sys/devices/system/node";
+
+    /// Parse a kernel cpu list such as `0-3,8-11,16`.
+    pub fn parse_list(list: &str) -> Option<Vec<usize>> {
+        let mut items = Vec::new();
+        for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+            match range.split_once('-') {
+                Some((start, end)) => {
+                    items.extend(start.parse::<usize>().ok()?..=end.parse().ok()?)
+                }
+                None => items.push(range.parse().ok()?),
+            }
+        }
+        Some(items)
+    }
+
+    /// The cores this thread is allowed to run on.
+    fn allowed_cpus() -> io::Result<Vec<usize>> {
+        // SAFETY: a zeroed cpu_set_t is a valid empty set, and the kernel writes at most its size
+        unsafe {
+            let mut set: libc::cpu_set_t = mem::zeroed();
+            if libc::sched_getaffinity(0, mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok((0..libc::CPU_SETSIZE as usize)
+                .filter(|&cpu| libc::CPU_ISSET(cpu, &set))
+                .collect())
+        }
+    }
+
+    /// The allowed cores on each NUMA node, sorted by node.
+    ///
+    /// Without NUMA information in `/sys`, all the allowed cores are on node 0.
+    pub fn topology() -> io::Result<Vec<(usize, Vec<usize>)>> {
+        let allowed = allowed_cpus()?;
+
+        let mut nodes = Vec::new();
+        if let Ok(entries) = fs::read_dir(NODES) {
+            for entry in entries.flatten() {
+                let name = entry.file_name();
+                let Some(node) = name
+                    .to_str()
+                    .and_then(|name| name.strip_prefix("node"))
+                    .and_then(|node| node.parse::<usize>().ok())
+                else {
+                    continue;
+                };
+                let Some(cpus) = fs::read_to_string(entry.path().join("cpulist"))
+                    .ok()
+                    .and_then(|list| parse_list(&list))
+                else {
+                    continue;
+                };
+                let cpus = cpus
+                    .into_iter()
+                    .filter(|cpu| allowed.contains(cpu))
+                    .collect::<Vec<_>>();
+                if !cpus.is_empty() {
+                    nodes.push((node, cpus));
+                }
+            }
+        }
+        nodes.sort_unstable();
+
+        if nodes.is_empty() {
+            nodes.push((0, allowed));
+        }
+        Ok(nodes)
+    }
+
+    pub fn pin_current_thread(cpu: usize) -> io::Result<()> {
+        // SAFETY: the set is a valid empty set before `cpu` is added, and only read by the kernel
+        unsafe {
+            let mut set: libc::cpu_set_t = mem::zeroed();
+            libc::CPU_SET(cpu, &mut set);
+            if libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn workers_spread_over_nodes() {
+        let topology = [(0, vec![0, 1, 2, 3]), (1, vec![8, 9, 10, 11])];
+
+        let placement = Placement::spread(4, &topology);
+        assert_eq!(placement.nodes(), [0, 0, 1, 1]);
+        assert_eq!(
+            placement.to_string(),
+            "node 0: workers 0-1 on cpus 0,2; node 1: workers 2-3 on cpus 8,10"
+        );
+
+        let placement = Placement::spread(12, &topology);
+        assert_eq!(
+            placement.nodes().iter().filter(|&&node| node == 1).count(),
+            6
+        );
+        assert_eq!(
+            placement.to_string(),
+            "node 0: workers 0-5 on cpus 0-3; node 1: workers 6-11 on cpus 8-11"
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn cpu_lists() {
+        assert_eq!(
+            linux::parse_list("0-3,8,10-11\n"),
+            Some(vec![0, 1, 2, 3, 8, 10, 11])
+        );
+        assert_eq!(linux::parse_list(""), Some(vec![]));
+        assert_eq!(linux::parse_list("0-x"), None);
+    }
+}
@@ -1,78 +1,221 @@
-use color_eyre::eyre::{eyre, Result};
-use indicatif::{ParallelProgressIterator, ProgressStyle};
+use color_eyre::eyre::{bail, eyre, Result};
+use indicatif::{ProgressBar, ProgressStyle};
 use rayon::prelude::*;
+use std::{fs::File, io::Read, path::Path};
+
+use crate::{
+    results::{self, Station},
+    table::StationTable,
+    temperature,
+};
 
 const TEMPLATE: &str =
-    "[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} ETA {eta_precise} ({per_sec})";
+    "[{elapsed_precise}] {bar:40.cyan/blue} {bytes:>10}/{total_bytes:10} ETA {eta_precise} ({bytes_per_sec})";
+
+/// Size of the line-aligned pieces a window is cut into before it is handed to rayon
+const PIECE_SIZE: usize = 1024 * 1024;
+/// Stations each piece's table starts with room for; a table grows if its piece has more.
+///
+/// rayon folds into a fresh table for every split, so sizing them for every possible station
+/// would allocate far more than the pieces ever fill.
+const PIECE_STATIONS: usize = 512;
 
+/// Measurements for one station, in integer tenths of a degree
 #[derive(Debug, Clone, Copy)]
 struct WeatherStationStatistics {
-    min: f64,
-    max: f64,
-    sum: f64,
-    count: usize,
+    min: i32,
+    max: i32,
+    sum: i64,
+    count: u64,
 }
 
 impl Default for WeatherStationStatistics {
     fn default() -> Self {
         Self {
-            min: f64::INFINITY,
-            max: f64::NEG_INFINITY,
-            sum: 0.0,
+            min: i32::MAX,
+            max: i32::MIN,
+            sum: 0,
             count: 0,
         }
     }
 }
 
-pub fn main() -> Result<()> {
-    color_eyre::install()?;
+impl WeatherStationStatistics {
+    fn update(&mut self, measurement: i32) {
+        self.min = self.min.min(measurement);
+        self.max = self.max.max(measurement);
+        self.sum += measurement as i64;
+        self.count += 1;
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+        self.count += other.count;
+    }
+}
 
-    let map = std::fs::read_to_string("../measurements.txt")?
-        .lines()
-        .collect::<Vec<_>>()
+/// Fill `buffer` from `file`, stopping early only at end of file.
+fn read_fill(file: &mut File, buffer: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match file.read(&mut buffer[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+/// Cut `window` into pieces of roughly [`PIECE_SIZE`] bytes which each end on a line boundary.
+fn line_aligned_pieces(mut window: &str) -> Vec<&str> {
+    let mut pieces = Vec::with_capacity(window.len() / PIECE_SIZE + 1);
+    while window.len() > PIECE_SIZE {
+        let end = match window.as_bytes()[PIECE_SIZE..]
+            .iter()
+            .position(|&b| b == b'\n')
+        {
+            Some(idx) => PIECE_SIZE + idx + 1,
+            None => window.len(),
+        };
+        let (piece, rest) = window.split_at(end);
+        pieces.push(piece);
+        window = rest;
+    }
+    if !window.is_empty() {
+        pieces.push(window);
+    }
+    pieces
+}
+
+/// Aggregate one window of complete lines in parallel.
+fn aggregate_window(
+    window: &str,
+    progress: &ProgressBar,
+) -> Result<StationTable<WeatherStationStatistics>> {
+    line_aligned_pieces(window)
         .into_par_iter()
-        .progress_with_style(ProgressStyle::default_bar().template(TEMPLATE)?)
-        .map(|line| -> Result<_> {
-            let (id, measurement) = line
-                .split_once(';')
-                .ok_or_else(|| eyre!("invalid line {line:?}"))?;
-            Ok((id, measurement.parse::<f64>()?))
-        })
-        .try_fold_with(
-            rustc_hash::FxHashMap::<String, WeatherStationStatistics>::default(),
-            |mut acc, res| -> Result<_> {
-                let (id, measurement) = res?;
-                let stats = acc.entry(id.to_string()).or_default();
-                stats.min = stats.min.min(measurement);
-                stats.max = stats.max.max(measurement);
-                stats.sum += measurement;
-                stats.count += 1;
+        .try_fold(
+            || StationTable::<WeatherStationStatistics>::with_capacity(PIECE_STATIONS),
+            |mut acc, piece| -> Result<_> {
+                for line in piece.lines() {
+                    let (id, measurement) = line
+                        .split_once(';')
+                        .ok_or_else(|| eyre!("invalid line {line:?}"))?;
+                    let measurement = temperature::parse_exact(measurement.as_bytes())
+                        .ok_or_else(|| eyre!("invalid temperature in line {line:?}"))?;
+                    acc.get_or_default(id.as_bytes()).update(measurement);
+                }
+                progress.inc(piece.len() as u64);
                 Ok(acc)
             },
         )
         .try_reduce(
-            || rustc_hash::FxHashMap::<String, WeatherStationStatistics>::default(),
-            |mut acc, map| -> Result<_> {
-                for (id, stats) in map {
-                    let acc_stats = acc.entry(id).or_default();
-                    acc_stats.min = acc_stats.min.min(stats.min);
-                    acc_stats.max = acc_stats.max.max(stats.max);
-                    acc_stats.sum += stats.sum;
-                    acc_stats.count += stats.count;
-                }
+            || StationTable::with_capacity(PIECE_STATIONS),
+            |mut acc, table| -> Result<_> {
+                acc.merge(table, |acc_stats, stats| acc_stats.merge(&stats));
                 Ok(acc)
             },
-        )?;
-
-    for (id, stats) in map {
-        println!(
-            "{}={}/{}/{}",
-            id,
-            stats.min,
-            stats.max,
-            stats.sum / stats.count as f64
-        );
+        )
+}
+
+/// Stream the measurements through a single buffer of `memory_cap` bytes.
+///
+/// Each refill of the buffer is cut back to its last complete line, aggregated by rayon, and the
+/// trailing partial line is carried over to the start of the next refill. The per-station maps are
+/// tiny compared to the buffer, so `memory_cap` bounds the memory used.
+fn aggregate(path: &Path, memory_cap: usize) -> Result<StationTable<WeatherStationStatistics>> {
+    let mut file = File::open(path)?;
+    let progress = ProgressBar::new(file.metadata()?.len())
+        .with_style(ProgressStyle::default_bar().template(TEMPLATE)?);
+
+    let mut map = StationTable::<WeatherStationStatistics>::new();
+    let mut buffer = vec![0; memory_cap];
+    let mut carried = 0;
+
+    loop {
+        let end = carried + read_fill(&mut file, &mut buffer[carried..])?;
+        let at_eof = end < buffer.len();
+
+        let complete = if at_eof {
+            end
+        } else {
+            match buffer[..end].iter().rposition(|&b| b == b'\n') {
+                Some(idx) => idx + 1,
+                None => bail!("a single line is longer than the memory cap of {memory_cap} bytes"),
+            }
+        };
+
+        let window = std::str::from_utf8(&buffer[..complete])?;
+        map.merge(aggregate_window(window, &progress)?, |acc_stats, stats| {
+            acc_stats.merge(&stats)
+        });
+
+        buffer.copy_within(complete..end, 0);
+        carried = end - complete;
+
+        if at_eof {
+            break;
+        }
     }
+    progress.finish();
+
+    Ok(map)
+}
+
+pub fn solve(path: &Path, memory_cap: usize) -> Result<Vec<Station>> {
+    let mut stations = aggregate(path, memory_cap)?
+        .into_entries()
+        .map(|(id, stats)| {
+            // every window was checked to be utf8 before it was aggregated
+            let station = String::from_utf8(id).expect("station names are utf8");
+            Station::from_tenths(
+                station,
+                stats.min.into(),
+                stats.sum,
+                stats.max.into(),
+                stats.count,
+            )
+        })
+        .collect::<Vec<_>>();
+    results::sort(&mut stations);
 
-    Ok(())
+    Ok(stations)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn small_memory_caps_match_one_window() {
+        let names = ["Abha", "Zürich", "Washington, D.C.", "A"];
+        let mut contents = String::new();
+        for row in 0..2_000_usize {
+            let tenths = (row * 7919 % 1999) as i64 - 999;
+            let sign = if tenths < 0 { "-" } else { "" };
+            let (whole, tenth) = (tenths.abs() / 10, tenths.abs() % 10);
+            contents += &format!("{};{sign}{whole}.{tenth}\n", names[row % names.len()]);
+        }
+        // the final partial line is only read at the end of the file
+        contents.pop();
+
+        let path =
+            std::env::temp_dir().join(format!("1brc-{}-purple-mist.txt", std::process::id()));
+        std::fs::write(&path, &contents).unwrap();
+
+        let expected = solve(&path, contents.len() + 1).unwrap();
+        assert_eq!(
+            expected.iter().map(|station| station.count).sum::<u64>(),
+            2_000
+        );
+        for memory_cap in [24, 100, 333, contents.len() - 1, contents.len()] {
+            assert_eq!(solve(&path, memory_cap).unwrap(), expected, "{memory_cap}");
+        }
+        // no room for the longest line
+        assert!(solve(&path, 20).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -0,0 +1,27 @@
+//! `1brc query "<SQL>"`: one-off questions about the raw measurements, answered by polars.
+//!
+//! ```sh
+//! 1brc query "SELECT station, AVG(measure) FROM measurements
+//!             WHERE station LIKE 'B%' AND measure > 30 GROUP BY station"
+//! ```
+
+use std::path::Path;
+
+use polars::sql::SQLContext;
+
+use crate::{
+    output::{self, Format},
+    scan_measurements, Result,
+};
+
+pub fn query(sql: &str, input: &Path, format: Format, output: Option<&Path>) -> Result<()> {
+    let mut context = SQLContext::new();
+    context.register("measurements", scan_measurements(input)?);
+
+    let mut df = context.execute(sql)?.with_streaming(true).collect()?;
+
+    match output {
+        Some(path) => output::write_dataframe_file(path, &mut df),
+        None => output::write_dataframe(&mut std::io::stdout().lock(), &mut df, format),
+    }
+}
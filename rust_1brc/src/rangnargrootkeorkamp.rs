@@ -5,17 +5,24 @@
     split_array,
     type_alias_impl_trait
 )]
-use clap::Parser;
-use colored::Colorize;
 use fxhash::FxHashMap;
 use memmap2::Mmap;
 use ptr_hash::PtrHashParams;
 use std::{
+    borrow::Cow,
+    ops::Range,
+    path::Path,
     simd::{cmp::SimdPartialEq, Simd},
-    thread::available_parallelism,
     vec::Vec,
 };
 
+use crate::{
+    fork,
+    placement::Placement,
+    results::{self, Station},
+    schedule::{self, Scheduler},
+};
+
 type V = i32;
 
 type PtrHash = ptr_hash::DefaultPtrHash<ptr_hash::hash::FxHash, u64>;
@@ -57,12 +64,9 @@ impl Record {
         let m = (1 << 21) - 1;
         ((self.sum & m) + 10 * ((self.sum >> 21) & m) + 100 * ((self.sum >> 42) & m)) as _
     }
-    /// Return (min, avg, max)
-    fn merge_pos_neg(pos: &Record, neg: &Record) -> (V, V, V) {
-        let pos_sum = pos.sum as V;
-        let neg_sum = neg.sum as V;
-        let sum = pos_sum - neg_sum;
-        let avg = sum / (pos.count + neg.count);
+    /// Return (min, sum, max)
+    fn merge_pos_neg(pos: &Record, neg: &Record) -> (V, i64, V) {
+        let sum = pos.sum as i64 - neg.sum as i64;
 
         let pos_max = raw_to_value(pos.max);
         let neg_max = -raw_to_value(!neg.min);
@@ -72,7 +76,7 @@ impl Record {
         let neg_min = -raw_to_value(neg.max);
         let min = pos_min.min(neg_min);
 
-        (min, avg, max)
+        (min, sum, max)
     }
 }
 
@@ -106,20 +110,14 @@ fn parse_pdep(data: &[u8], start: usize, end: usize) -> u64 {
     v & mask
 }
 
-fn format(v: V) -> String {
-    format!("{:.1}", v as f64 / 10.0)
-}
-
 #[allow(unused)]
 fn to_key(name: &[u8]) -> u64 {
-    // Hash the first and last 8 bytes.
-    let head: [u8; 8] = unsafe { *name.get_unchecked(..8).split_array_ref().0 };
-    let tail: [u8; 8] = unsafe {
-        *name
-            .get_unchecked(name.len().wrapping_sub(8)..)
-            .split_array_ref()
-            .0
-    };
+    // Hash the first and last 8 bytes. Shorter names are copied in, rather than reading past them:
+    // the bytes around the name are shifted out below either way.
+    let (mut head, mut tail) = ([0; 8], [0; 8]);
+    let len = name.len().min(8);
+    head[..len].copy_from_slice(&name[..len]);
+    tail[8 - len..].copy_from_slice(&name[name.len() - len..]);
     let shift = 64usize.saturating_sub(8 * name.len());
     let khead = u64::from_ne_bytes(head) << shift;
     let ktail = u64::from_ne_bytes(tail) >> shift;
@@ -156,7 +154,7 @@ fn iter_lines<'a>(mut data: &'a [u8], mut callback: impl FnMut(&'a [u8], usize,
         let first_end = idx + data[idx..].iter().position(|&c| c == b'\n').unwrap();
         State {
             sep_pos: first_end,
-            start_pos: first_end,
+            start_pos: first_end + 1,
         }
     };
 
@@ -180,9 +178,7 @@ fn iter_lines<'a>(mut data: &'a [u8], mut callback: impl FnMut(&'a [u8], usize,
     }
 }
 
-pub fn run<'a>(data: &'a [u8], phf: &'a PtrHash, num_slots: usize) -> Vec<Record> {
-    // Each thread has its own accumulator.
-    let mut slots = vec![Record::default(); num_slots];
+fn run(data: &[u8], phf: &PtrHash, slots: &mut [Record]) {
     iter_lines(data, |data, start, mut sep, end| {
         unsafe {
             // If value is negative, extend name by one character.
@@ -197,28 +193,55 @@ pub fn run<'a>(data: &'a [u8], phf: &'a PtrHash, num_slots: usize) -> Vec<Record
             );
         }
     });
-    slots
 }
 
-pub fn run_parallel(
+/// The part of `data` to hand to [`iter_lines`] for the lines owned by `chunk`.
+///
+/// `iter_lines` skips ahead to the first newline and stops [`L`] bytes short of the end, so the
+/// slice starts on the newline before the chunk's first line and runs `L` bytes past its last one.
+/// `to_key` also reads up to 8 bytes before a short name. At the start and end of the file, where
+/// there are no such bytes to spare, the lines are copied into a buffer padded on both sides.
+fn chunk_lines(data: &[u8], chunk: Range<u64>) -> Option<Cow<'_, [u8]>> {
+    let lines = schedule::align(data, chunk);
+    if lines.is_empty() {
+        return None;
+    }
+    let start = lines.as_ptr() as usize - data.as_ptr() as usize;
+    let end = start + lines.len();
+    if start >= L && end + L <= data.len() {
+        return Some(Cow::Borrowed(&data[start - 1..end + L]));
+    }
+
+    let mut padded = vec![0; L];
+    padded[L - 1] = b'\n';
+    padded.extend_from_slice(lines);
+    // newlines, so a last line without one still ends inside the buffer
+    padded.resize(padded.len() + L, b'\n');
+    Some(Cow::Owned(padded))
+}
+
+fn run_parallel(
     data: &[u8],
     phf: &PtrHash,
     num_slots: usize,
-    num_threads: usize,
+    placement: &Placement,
 ) -> Vec<Record> {
-    if num_threads == 0 {
-        return run(data, phf, num_slots);
-    }
-
+    let scheduler = Scheduler::new(data.len() as u64, placement.nodes(), schedule::MAX_CHUNK);
     let slots = std::sync::Mutex::new(vec![Record::default(); num_slots]);
 
-    // Spawn one thread per core.
+    // Spawn one thread per worker.
     std::thread::scope(|s| {
-        let chunks = data.chunks(data.len() / num_threads + 1);
-        for chunk in chunks {
-            s.spawn(|| {
+        for worker in 0..scheduler.workers() {
+            let (scheduler, slots) = (&scheduler, &slots);
+            s.spawn(move || {
+                placement.pin(worker);
                 // Each thread has its own accumulator.
-                let thread_slots = run(chunk, phf, num_slots);
+                let mut thread_slots = vec![Record::default(); num_slots];
+                while let Some(chunk) = scheduler.next(worker) {
+                    if let Some(lines) = chunk_lines(data, chunk) {
+                        run(&lines, phf, &mut thread_slots);
+                    }
+                }
 
                 // Merge results.
                 let mut slots = slots.lock().unwrap();
@@ -290,24 +313,12 @@ fn build_perfect_hash(data: &[u8]) -> (Vec<Vec<u8>>, PtrHash, usize) {
     (names, ptrhash, num_slots)
 }
 
-#[derive(clap::Parser)]
-struct Args {
-    input: Option<String>,
-
-    #[clap(short = 'j', long)]
-    threads: Option<usize>,
-
-    #[clap(long)]
-    print: bool,
-}
-
-pub fn main() {
-    let start = std::time::Instant::now();
-    let filename = ("../measurements.txt".to_string());
+/// Aggregate the file at `path` with one thread per worker in `placement`.
+fn aggregate(path: &Path, placement: &Placement) -> Vec<Station> {
     let mmap: Mmap;
     let data;
     {
-        let file = std::fs::File::open(filename).unwrap();
+        let file = std::fs::File::open(path).unwrap();
         mmap = unsafe { Mmap::map(&file).unwrap() };
         data = &*mmap;
     }
@@ -317,45 +328,43 @@ pub fn main() {
     let data = &data[offset..];
 
     // Build a perfect hash function on the cities found in the first 100k characters.
-    let (names, phf, num_slots) = build_perfect_hash(&data[..100000]);
-
-    let records = run_parallel(
-        data,
-        &phf,
-        num_slots,
-        args.threads
-            .unwrap_or(available_parallelism().unwrap().into()),
-    );
-
-    if args.print {
-        for name in &names {
-            if *name.last().unwrap() != b';' {
-                continue;
-            }
-            let namepos = &name[..name.len() - 1];
-            let kpos = to_key(namepos);
-            let kneg = to_key(name);
-
-            let idxpos = phf.index_single_part(&kpos);
-            let idxneg = phf.index_single_part(&kneg);
-            let rpos = &records.get(idxpos).unwrap();
-            let rneg = &records.get(idxneg).unwrap();
-            let (min, avg, max) = Record::merge_pos_neg(rpos, rneg);
-            eprintln!(
-                "{}: {}/{}/{}",
-                to_str(namepos),
-                format(min),
-                format(avg),
-                format(max)
-            );
+    let Some(sample) = chunk_lines(data, 0..data.len().min(100_000) as u64) else {
+        fork::release(mmap);
+        return Vec::new();
+    };
+    let (names, phf, num_slots) = build_perfect_hash(&sample);
+
+    let records = run_parallel(data, &phf, num_slots, placement);
+
+    let mut stations = Vec::new();
+    for name in &names {
+        if *name.last().unwrap() != b';' {
+            continue;
         }
-        eprintln!("done");
+        let namepos = &name[..name.len() - 1];
+        let kpos = to_key(namepos);
+        let kneg = to_key(name);
+
+        let idxpos = phf.index_single_part(&kpos);
+        let idxneg = phf.index_single_part(&kneg);
+        let rpos = &records.get(idxpos).unwrap();
+        let rneg = &records.get(idxneg).unwrap();
+        let (min, sum, max) = Record::merge_pos_neg(rpos, rneg);
+        stations.push(Station::from_tenths(
+            to_str(namepos).to_owned(),
+            min as i64,
+            sum,
+            max as i64,
+            (rpos.count + rneg.count) as u64,
+        ));
     }
+    fork::release(mmap);
+    results::sort(&mut stations);
+    stations
+}
 
-    eprintln!(
-        "total: {}",
-        format!("{:>5.2?}", start.elapsed()).bold().green()
-    );
+pub fn solve(path: &Path, placement: &Placement) -> Vec<Station> {
+    aggregate(path, placement)
 }
 
 #[cfg(test)]
@@ -0,0 +1,38 @@
+//! The result type every solver boils its output down to.
+
+/// Aggregated measurements for a single station, in degrees.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Station {
+    pub station: String,
+    pub min: f64,
+    pub mean: f64,
+    pub max: f64,
+    pub count: u64,
+}
+
+impl Station {
+    /// Build a station from aggregates kept in integer tenths of a degree.
+    ///
+    /// Every solver goes through here, so they all round the mean the same way.
+    pub fn from_tenths(station: String, min: i64, sum: i64, max: i64, count: u64) -> Self {
+        Self {
+            station,
+            min: min as f64 / 10.0,
+            mean: mean_tenths(sum, count) as f64 / 10.0,
+            max: max as f64 / 10.0,
+            count,
+        }
+    }
+}
+
+/// The mean of `count` measurements summing to `sum` tenths, in tenths, rounded half up like
+/// Java's `Math.round` in the reference solution.
+pub fn mean_tenths(sum: i64, count: u64) -> i64 {
+    let count = count as i64;
+    (2 * sum + count).div_euclid(2 * count)
+}
+
+/// Sort stations by name, which is the order all results are reported in.
+pub fn sort(stations: &mut [Station]) {
+    stations.sort_unstable_by(|a, b| a.station.cmp(&b.station));
+}
@@ -0,0 +1,232 @@
+//! Handing out chunks of a file to worker threads.
+//!
+//! The file is split evenly into one queue of bytes per worker. A worker takes chunks off the
+//! front of its own queue, sized by guided self-scheduling: an eighth of what's left in the queue,
+//! clamped between [`MIN_CHUNK`] and the caller's maximum, so chunks shrink as the queue drains.
+//! Once its own queue is empty it steals the back half of the fullest other queue, so nobody sits
+//! idle while there is work left. Workers steal from the other workers on their own NUMA node
+//! first, and only cross to another node once their node has run out of work.
+//!
+//! Chunks are plain byte ranges. A chunk owns the lines whose newline falls inside it, which
+//! [`align`] cuts out for solvers with the whole file in memory.
+//!
+//! Each worker keeps its results to itself until the end of the run, when [`reduce`] combines them.
+
+use std::{ops::Range, sync::Mutex, thread};
+
+/// Smallest chunk handed out, unless less than that is left
+#[cfg(not(test))]
+pub const MIN_CHUNK: u64 = 1024 * 1024;
+/// Tests hand out far smaller chunks, so that their small files still take many chunks per worker
+/// and exercise stealing, the shrinking chunk sizes and the readers' reuse of their buffers.
+#[cfg(test)]
+pub const MIN_CHUNK: u64 = 4 * 1024;
+/// Largest chunk handed out to solvers that read straight from a mapped file
+pub const MAX_CHUNK: u64 = 16 * 1024 * 1024;
+
+/// One worker per core, unless told otherwise.
+pub fn threads(requested: Option<usize>) -> usize {
+    requested
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, Into::into))
+        .max(1)
+}
+
+pub struct Scheduler {
+    queues: Vec<Mutex<Range<u64>>>,
+    /// The NUMA node of each worker
+    nodes: Vec<usize>,
+    max_chunk: u64,
+    end: u64,
+}
+
+impl Scheduler {
+    /// Split `len` bytes between one worker per entry of `nodes`, which is the NUMA node each
+    /// worker runs on. Workers on the same node should be numbered consecutively, so that each
+    /// node starts out with one contiguous part of the file.
+    pub fn new(len: u64, nodes: &[usize], max_chunk: u64) -> Self {
+        let nodes = if nodes.is_empty() {
+            vec![0]
+        } else {
+            nodes.to_vec()
+        };
+        let workers = nodes.len() as u64;
+        Self {
+            queues: (0..workers)
+                .map(|worker| Mutex::new(len * worker / workers..len * (worker + 1) / workers))
+                .collect(),
+            nodes,
+            max_chunk: max_chunk.max(MIN_CHUNK),
+            end: len,
+        }
+    }
+
+    pub fn workers(&self) -> usize {
+        self.queues.len()
+    }
+
+    /// Whether `chunk` is the one at the end of the file.
+    pub fn ends_file(&self, chunk: &Range<u64>) -> bool {
+        chunk.end == self.end
+    }
+
+    /// The next chunk for `worker`, or `None` once the whole file has been handed out.
+    pub fn next(&self, worker: usize) -> Option<Range<u64>> {
+        loop {
+            if let Some(chunk) = self.claim(worker) {
+                return Some(chunk);
+            }
+            if !self.steal(worker) {
+                return None;
+            }
+        }
+    }
+
+    fn claim(&self, worker: usize) -> Option<Range<u64>> {
+        let mut queue = self.queues[worker].lock().expect("non-poisoned mutex");
+        if queue.is_empty() {
+            return None;
+        }
+        let remaining = queue.end - queue.start;
+        let size = (remaining / 8)
+            .clamp(MIN_CHUNK, self.max_chunk)
+            .min(remaining);
+        let chunk = queue.start..queue.start + size;
+        queue.start += size;
+        Some(chunk)
+    }
+
+    /// Move the back half of the fullest other queue into `thief`'s (empty) queue, preferring
+    /// queues on the thief's own node.
+    ///
+    /// Returns `false` once every other queue is empty. Only one lock is ever held at a time, so the
+    /// victim may be emptied by its owner between the scan and the steal; then the queues are
+    /// scanned again.
+    fn steal(&self, thief: usize) -> bool {
+        let remaining = |worker: usize| {
+            let queue = self.queues[worker].lock().expect("non-poisoned mutex");
+            queue.end - queue.start
+        };
+        loop {
+            let Some((victim, _)) = (0..self.queues.len())
+                .filter(|&worker| worker != thief)
+                .map(|worker| (worker, remaining(worker)))
+                .filter(|&(_, remaining)| remaining > 0)
+                .max_by_key(|&(worker, remaining)| {
+                    let same_node = self.nodes[worker] == self.nodes[thief];
+                    (same_node, remaining)
+                })
+            else {
+                return false;
+            };
+
+            let mut queue = self.queues[victim].lock().expect("non-poisoned mutex");
+            let remaining = queue.end - queue.start;
+            if remaining == 0 {
+                continue;
+            }
+            let take = if remaining >= 2 * MIN_CHUNK {
+                remaining / 2
+            } else {
+                remaining
+            };
+            let stolen = queue.end - take..queue.end;
+            queue.end -= take;
+            drop(queue);
+
+            *self.queues[thief].lock().expect("non-poisoned mutex") = stolen;
+            return true;
+        }
+    }
+}
+
+/// The lines of `data` owned by the chunk `range`: those whose newline falls inside it.
+///
+/// The chunk at the end of the file also gets a final line without a newline.
+pub fn align(data: &[u8], range: Range<u64>) -> &[u8] {
+    let (start, end) = (range.start as usize, range.end as usize);
+    let line_after_newline_before = |idx: usize| {
+        data[..idx]
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |newline| newline + 1)
+    };
+
+    let head = line_after_newline_before(start);
+    let tail = if end == data.len() {
+        end
+    } else {
+        line_after_newline_before(end)
+    };
+    &data[head..tail.max(head)]
+}
+
+/// Combine the workers' results pairwise, merging every pair of a round on its own thread.
+///
+/// Takes `⌈log2 n⌉` rounds for `n` results, so no single thread merges more than that many times.
+pub fn reduce<T: Send>(mut results: Vec<T>, merge: impl Fn(&mut T, T) + Sync) -> Option<T> {
+    while results.len() > 1 {
+        let upper = results.split_off(results.len().div_ceil(2));
+        thread::scope(|scope| {
+            for (into, from) in results.iter_mut().zip(upper) {
+                let merge = &merge;
+                scope.spawn(move || merge(into, from));
+            }
+        });
+    }
+    results.pop()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn chunks_cover_the_file_once() {
+        let len = 37 * MIN_CHUNK + 12345;
+        let scheduler = Scheduler::new(len, &[0, 0, 1, 1, 1], 4 * MIN_CHUNK);
+        let mut chunks = std::thread::scope(|scope| {
+            let handles = (0..scheduler.workers())
+                .map(|worker| {
+                    let scheduler = &scheduler;
+                    scope.spawn(move || {
+                        std::iter::from_fn(|| scheduler.next(worker)).collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        chunks.sort_by_key(|chunk| chunk.start);
+        assert_eq!(chunks.first().unwrap().start, 0);
+        assert_eq!(chunks.last().unwrap().end, len);
+        for pair in chunks.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+        }
+    }
+
+    #[test]
+    fn aligned_chunks_split_lines_once() {
+        let data = b"a;1.0\nbb;-2.5\nccc;3.0\nd;4.0";
+        let mut joined = Vec::new();
+        for cut in [0, 3, 7, 8, 20, data.len()].windows(2) {
+            joined.extend_from_slice(align(data, cut[0] as u64..cut[1] as u64));
+        }
+        assert_eq!(joined, data);
+    }
+
+    #[test]
+    fn reduce_merges_everything() {
+        for workers in 0..20 {
+            let total = reduce(
+                (0..workers).map(|worker| vec![worker]).collect(),
+                |into, from| into.extend(from),
+            );
+            let mut total = total.unwrap_or_default();
+            total.sort_unstable();
+            assert_eq!(total, (0..workers).collect::<Vec<_>>());
+        }
+    }
+}
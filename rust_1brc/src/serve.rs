@@ -0,0 +1,210 @@
+//! `1brc serve`: answer queries about the aggregated results over HTTP.
+//!
+//! The results are computed once at startup and kept in memory, sorted by station name.
+//!
+//! - `GET /stations` lists every station
+//! - `GET /stations/{name}` returns a single station (`name` is percent-decoded)
+//! - `GET /top?by=max&k=10` returns the `k` stations with the highest `min`, `mean`, `max` or
+//!   `count`; add `order=asc` for the lowest instead
+//! - `POST /reload` re-runs the solver against the input file
+
+use std::io::Cursor;
+
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{results::Station, solve, Result, Solver, SolverOptions};
+
+type HttpResponse = Response<Cursor<Vec<u8>>>;
+
+fn json_response(status: u16, body: &impl serde::Serialize) -> HttpResponse {
+    let body = serde_json::to_vec(body).expect("results always serialize to json");
+    Response::from_data(body)
+        .with_status_code(status)
+        .with_header(
+            Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+                .expect("static header is valid"),
+        )
+}
+
+fn error_response(status: u16, message: impl std::fmt::Display) -> HttpResponse {
+    json_response(status, &serde_json::json!({ "error": message.to_string() }))
+}
+
+/// Decode `%XX` escapes in a path segment.
+///
+/// Returns `None` for malformed escapes or if the decoded bytes are not UTF-8.
+fn percent_decode(segment: &str) -> Option<String> {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if bytes[idx] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(idx + 1..idx + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            idx += 3;
+        } else {
+            decoded.push(bytes[idx]);
+            idx += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+fn station(stations: &[Station], name: &str) -> HttpResponse {
+    let Some(name) = percent_decode(name) else {
+        return error_response(400, "malformed station name");
+    };
+    match stations.binary_search_by(|station| station.station.as_str().cmp(&name)) {
+        Ok(idx) => json_response(200, &stations[idx]),
+        Err(_) => error_response(404, format!("no station named {name:?}")),
+    }
+}
+
+/// The stations a `/top` query asks for, or why the query is malformed.
+fn rank<'a>(stations: &'a [Station], query: &str) -> std::result::Result<Vec<&'a Station>, String> {
+    let mut by = "max";
+    let mut k = 10;
+    let mut ascending = false;
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        match pair.split_once('=') {
+            Some(("by", value)) => by = value,
+            Some(("k", value)) => match value.parse() {
+                Ok(value) => k = value,
+                Err(err) => return Err(format!("parsing k={value}: {err}")),
+            },
+            Some(("order", "asc")) => ascending = true,
+            Some(("order", "desc")) => ascending = false,
+            _ => return Err(format!("unexpected query parameter {pair:?}")),
+        }
+    }
+
+    let key: fn(&Station) -> f64 = match by {
+        "min" => |station| station.min,
+        "mean" => |station| station.mean,
+        "max" => |station| station.max,
+        "count" => |station| station.count as f64,
+        _ => return Err(format!("cannot rank stations by {by:?}")),
+    };
+
+    let mut ranked = stations.iter().collect::<Vec<_>>();
+    ranked.sort_by(|a, b| key(a).total_cmp(&key(b)));
+    if !ascending {
+        ranked.reverse();
+    }
+    ranked.truncate(k);
+    Ok(ranked)
+}
+
+fn top(stations: &[Station], query: &str) -> HttpResponse {
+    match rank(stations, query) {
+        Ok(ranked) => json_response(200, &ranked),
+        Err(err) => error_response(400, err),
+    }
+}
+
+/// Re-run the solver, turning a panic into an error so that a bad input file can't take the
+/// server down with it.
+fn reload(solver: Solver, options: &SolverOptions) -> Result<Vec<Station>> {
+    std::panic::catch_unwind(|| solve(solver, options))
+        .unwrap_or_else(|_| Err(format!("the {solver:?} solver panicked").into()))
+}
+
+fn handle(
+    request: &Request,
+    stations: &mut Vec<Station>,
+    solver: Solver,
+    options: &SolverOptions,
+) -> HttpResponse {
+    let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+
+    match (request.method(), path) {
+        (Method::Get, "/stations") => json_response(200, stations),
+        (Method::Get, "/top") => top(stations, query),
+        (Method::Get, path) if path.starts_with("/stations/") => {
+            station(stations, &path["/stations/".len()..])
+        }
+        (Method::Post, "/reload") => match reload(solver, options) {
+            Ok(reloaded) => {
+                *stations = reloaded;
+                json_response(200, &serde_json::json!({ "stations": stations.len() }))
+            }
+            Err(err) => error_response(500, format!("reloading: {err}")),
+        },
+        _ => error_response(404, format!("no route for {} {path}", request.method())),
+    }
+}
+
+pub fn serve(port: u16, solver: Solver, options: &SolverOptions) -> Result<()> {
+    let mut stations = solve(solver, options)?;
+
+    let server =
+        Server::http(("127.0.0.1", port)).map_err(|err| format!("binding port {port}: {err}"))?;
+    eprintln!(
+        "serving {} stations on http://127.0.0.1:{port}",
+        stations.len()
+    );
+
+    for request in server.incoming_requests() {
+        let response = handle(&request, &mut stations, solver, options);
+        // a client hanging up mid-response is its own problem, not the server's
+        if let Err(err) = request.respond(response) {
+            eprintln!("responding: {err}");
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn station_names_are_percent_decoded() {
+        assert_eq!(percent_decode("Hamburg").as_deref(), Some("Hamburg"));
+        assert_eq!(
+            percent_decode("Addis%20Ababa").as_deref(),
+            Some("Addis Ababa")
+        );
+        assert_eq!(
+            percent_decode("Ab%C3%A9ch%c3%a9").as_deref(),
+            Some("Abéché")
+        );
+        assert_eq!(percent_decode("100%"), None);
+        assert_eq!(percent_decode("%4"), None);
+        assert_eq!(percent_decode("%zz"), None);
+        assert_eq!(percent_decode("%ff"), None);
+    }
+
+    #[test]
+    fn top_queries() {
+        let stations = [("Abha", 1.0, 120), ("Bulawayo", 3.0, 5), ("Cairo", 2.0, 40)].map(
+            |(name, mean, count)| Station {
+                station: name.to_owned(),
+                min: mean - 1.0,
+                mean,
+                max: mean + 1.0,
+                count,
+            },
+        );
+        let names = |query| {
+            rank(&stations, query).map(|ranked| {
+                ranked
+                    .into_iter()
+                    .map(|station| station.station.as_str())
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        assert_eq!(names(""), Ok(vec!["Bulawayo", "Cairo", "Abha"]));
+        assert_eq!(names("by=count&k=2"), Ok(vec!["Abha", "Cairo"]));
+        assert_eq!(names("k=1&by=mean&order=asc"), Ok(vec!["Abha"]));
+        assert_eq!(
+            names("by=min&order=desc&k=10"),
+            Ok(vec!["Bulawayo", "Cairo", "Abha"])
+        );
+        for bad in ["by=name", "k=-1", "k=", "order=up", "limit=3", "by"] {
+            assert!(names(bad).is_err(), "{bad}");
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! A hash table specialised for station names, shared by the std, thebracket and purple_mist
+//! solvers.
+//!
+//! The general purpose maps all pay for things a station table doesn't need: feeding every key
+//! through a `Hasher`, a separately allocated key per entry, and a full key comparison on every
+//! probe. Here every slot holds the aggregate for its station next to a tag made of the
+//! name's length and first seven bytes, which settles most comparisons on its own, and names of
+//! up to [`INLINE`] bytes are stored in the slot as well. Slots are probed linearly in a
+//! power-of-two array that starts out big enough for [`MAX_STATIONS`] at under half full.
+
+/// The most stations the challenge allows
+pub const MAX_STATIONS: usize = 10_000;
+/// Names up to this many bytes are kept in the slot; longer ones get their own allocation
+pub const INLINE: usize = 24;
+
+const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+#[derive(Debug, Clone)]
+enum Name {
+    Inline([u8; INLINE]),
+    Spilled(Box<[u8]>),
+}
+
+#[derive(Debug, Clone)]
+struct Slot<A> {
+    /// The name's length plus one in the low byte (saturating), its first seven bytes above that.
+    /// Zero for an empty slot.
+    tag: u64,
+    name: Name,
+    value: A,
+}
+
+impl<A: Default> Default for Slot<A> {
+    fn default() -> Self {
+        Self {
+            tag: 0,
+            name: Name::Inline([0; INLINE]),
+            value: A::default(),
+        }
+    }
+}
+
+impl<A> Slot<A> {
+    fn name(&self) -> &[u8] {
+        match &self.name {
+            Name::Inline(bytes) => &bytes[..(self.tag & 0xff) as usize - 1],
+            Name::Spilled(bytes) => bytes,
+        }
+    }
+
+    /// Whether the slot holds `name`, whose tag is `tag`.
+    fn holds(&self, tag: u64, name: &[u8]) -> bool {
+        if self.tag != tag {
+            return false;
+        }
+        // equal tags mean equal lengths, and for short names equal bytes too
+        match &self.name {
+            Name::Inline(bytes) => name.len() <= 7 || same(&bytes[7..name.len()], &name[7..]),
+            Name::Spilled(bytes) => **bytes == *name,
+        }
+    }
+}
+
+/// The first eight bytes of `name` (or all of it) as a little-endian word, zero padded.
+///
+/// Short names are read with overlapping loads rather than copied into a buffer.
+fn word(name: &[u8]) -> u64 {
+    let len = name.len();
+    let load = |at: usize| u32::from_le_bytes(name[at..at + 4].try_into().expect("4 bytes")) as u64;
+    match len {
+        8.. => u64::from_le_bytes(name[..8].try_into().expect("8 bytes")),
+        4..=7 => load(0) | load(len - 4) << (8 * (len - 4)),
+        1..=3 => {
+            name[0] as u64
+                | (name[len / 2] as u64) << (8 * (len / 2))
+                | (name[len - 1] as u64) << (8 * (len - 1))
+        }
+        0 => 0,
+    }
+}
+
+/// Compare two names of the same length a word at a time, which beats `memcmp` for short ones.
+fn same(a: &[u8], b: &[u8]) -> bool {
+    let len = a.len();
+    let mut at = 0;
+    while at + 8 < len {
+        if word(&a[at..]) != word(&b[at..]) {
+            return false;
+        }
+        at += 8;
+    }
+    let last = len.saturating_sub(8);
+    word(&a[last..]) == word(&b[last..])
+}
+
+fn tag(name: &[u8]) -> u64 {
+    word(name) << 8 | (name.len().min(254) as u64 + 1)
+}
+
+fn mix(hash: u64, word: u64) -> u64 {
+    (hash.rotate_left(5) ^ word).wrapping_mul(SEED)
+}
+
+/// FxHash over whole words; the slot is picked from the top bits, which are the best mixed.
+fn hash(name: &[u8]) -> u64 {
+    let len = name.len();
+    let mut hash = mix(len as u64, word(name));
+    if len > 8 {
+        let mut at = 8;
+        while at + 8 < len {
+            hash = mix(hash, word(&name[at..]));
+            at += 8;
+        }
+        // the last eight bytes, which may overlap the word before
+        hash = mix(hash, word(&name[len - 8..]));
+    }
+    hash
+}
+
+/// Station names mapped to aggregates of type `A`, which start out as `A::default()`.
+#[derive(Debug, Clone)]
+pub struct StationTable<A> {
+    slots: Box<[Slot<A>]>,
+    /// `64 - log2(slots.len())`, to take the top bits of a hash
+    shift: u32,
+    len: usize,
+}
+
+impl<A: Default> Default for StationTable<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: Default> StationTable<A> {
+    /// A table with room for [`MAX_STATIONS`].
+    pub fn new() -> Self {
+        Self::with_capacity(MAX_STATIONS)
+    }
+
+    /// A table that holds `stations` without growing.
+    pub fn with_capacity(stations: usize) -> Self {
+        let slots = (2 * stations).next_power_of_two().max(16);
+        Self {
+            slots: (0..slots).map(|_| Slot::default()).collect(),
+            shift: 64 - slots.trailing_zeros(),
+            len: 0,
+        }
+    }
+
+    /// Find `name`'s slot, or the empty slot where it would go.
+    fn probe(&self, tag: u64, name: &[u8]) -> usize {
+        let mask = self.slots.len() - 1;
+        let mut idx = (hash(name) >> self.shift) as usize;
+        loop {
+            let slot = &self.slots[idx];
+            if slot.tag == 0 || slot.holds(tag, name) {
+                return idx;
+            }
+            idx = (idx + 1) & mask;
+        }
+    }
+
+    /// The aggregate for `name`, inserting a default one if it is new.
+    pub fn get_or_default(&mut self, name: &[u8]) -> &mut A {
+        let tag = tag(name);
+        let mut idx = self.probe(tag, name);
+        if self.slots[idx].tag == 0 {
+            // keep the table at most half full, so probe sequences stay short
+            if 2 * (self.len + 1) > self.slots.len() {
+                self.grow();
+                idx = self.probe(tag, name);
+            }
+            let slot = &mut self.slots[idx];
+            slot.tag = tag;
+            slot.name = if name.len() <= INLINE {
+                let mut bytes = [0; INLINE];
+                bytes[..name.len()].copy_from_slice(name);
+                Name::Inline(bytes)
+            } else {
+                Name::Spilled(name.into())
+            };
+            self.len += 1;
+        }
+        &mut self.slots[idx].value
+    }
+
+    fn grow(&mut self) {
+        let old = std::mem::replace(self, Self::with_capacity(self.slots.len()));
+        for slot in old
+            .slots
+            .into_vec()
+            .into_iter()
+            .filter(|slot| slot.tag != 0)
+        {
+            let idx = self.probe(slot.tag, slot.name());
+            self.slots[idx] = slot;
+            self.len += 1;
+        }
+    }
+
+    /// Fold every station of `other` into this table with `merge`.
+    pub fn merge(&mut self, other: Self, mut merge: impl FnMut(&mut A, A)) {
+        for slot in other
+            .slots
+            .into_vec()
+            .into_iter()
+            .filter(|slot| slot.tag != 0)
+        {
+            let name = slot.name();
+            merge(self.get_or_default(name), slot.value);
+        }
+    }
+
+    /// Take every station's name and aggregate out of the table, in no particular order.
+    pub fn into_entries(self) -> impl Iterator<Item = (Vec<u8>, A)> {
+        self.slots
+            .into_vec()
+            .into_iter()
+            .filter(|slot| slot.tag != 0)
+            .map(|slot| (slot.name().to_vec(), slot.value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stations_are_kept_apart() {
+        // short, inline and spilled names, sharing prefixes and lengths, with enough of them to
+        // make the table grow
+        let names = (0..3 * MAX_STATIONS)
+            .map(|idx| format!("{:x<width$}{idx}", "Station", width = idx % 40).into_bytes())
+            .chain((0..100).map(|idx| idx.to_string().into_bytes()))
+            .chain([Vec::new(), vec![b'y'; 300], b"\xff\xfe".to_vec()])
+            .collect::<Vec<_>>();
+
+        let mut table = StationTable::<u64>::with_capacity(16);
+        for _ in 0..2 {
+            for (idx, name) in names.iter().enumerate() {
+                *table.get_or_default(name) += idx as u64;
+            }
+        }
+
+        let mut merged = StationTable::new();
+        merged.merge(table.clone(), |into, from| *into += from);
+        merged.merge(table, |into, from| *into += from);
+        let mut entries = merged.into_entries().collect::<Vec<_>>();
+        entries.sort_unstable_by_key(|(_, value)| *value);
+        let expected = names
+            .into_iter()
+            .enumerate()
+            .map(|(idx, name)| (name, 4 * idx as u64))
+            .collect::<Vec<_>>();
+        assert_eq!(entries, expected);
+    }
+}
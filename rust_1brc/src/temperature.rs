@@ -0,0 +1,119 @@
+//! Parsing temperatures into integer tenths of a degree, eight bytes at a time.
+//!
+//! A temperature is always `-?\d?\d\.\d`, so all of it fits in one `u64` read straight from the
+//! input. The `.` is the only one of the bytes after the first without bit `0x10` set, which gives
+//! its position, and the first byte is `-` exactly when it has that bit clear too. Shifting the
+//! word by the position of the `.` lines the digits up in fixed bytes, and a single multiplication
+//! then adds them up with their place values. This is the SWAR ("SIMD within a register") trick
+//! from the fastest Java entries to the challenge.
+
+/// Bit `0x10` of bytes 1 to 3, one of which holds the `.`
+const DOT_BITS: u64 = 0x1010_1000;
+/// The tens, units and tenths digits once [`parse_word`] has lined them up
+const DIGIT_BYTES: u64 = 0x0f_000f_0f00;
+/// Multiplies the tens, units and tenths into bits 32 and up, as 100s, 10s and 1s
+const PLACE_VALUES: u64 = 0x640a_0001;
+const ZEROS: u64 = 0x3030_3030_3030_3030;
+
+/// The temperature at the start of `word`, in tenths, and the number of bytes it takes up.
+///
+/// `word` holds the input bytes in little-endian order. Anything after the temperature is ignored.
+#[inline]
+fn parse_word(word: u64) -> (i32, usize) {
+    let dot = (!word & DOT_BITS).trailing_zeros();
+    // all ones for a `-`, all zeros for a digit
+    let signed = ((!word << 59) as i64) >> 63;
+    let unsigned = word & !(signed as u64 & 0xff);
+    let digits = (unsigned << (28u32.wrapping_sub(dot) % 64)) & DIGIT_BYTES;
+    let abs = (digits.wrapping_mul(PLACE_VALUES) >> 32) & 0x3ff;
+    let tenths = (abs as i64 ^ signed) - signed;
+    (tenths as i32, (dot as usize >> 3) + 2)
+}
+
+/// The first eight bytes of `bytes` as a little-endian word, padded with zeros if there are fewer.
+#[inline]
+fn load(bytes: &[u8]) -> u64 {
+    match bytes.get(..8) {
+        Some(word) => u64::from_le_bytes(word.try_into().expect("8 bytes")),
+        None => {
+            let mut word = [0; 8];
+            word[..bytes.len()].copy_from_slice(bytes);
+            u64::from_le_bytes(word)
+        }
+    }
+}
+
+/// Parse the temperature that `bytes` starts with, returning it in tenths of a degree and the
+/// number of bytes it takes up.
+///
+/// `bytes` may run on past the temperature, and is best given with at least eight bytes so it can
+/// be read in one go. It must start with a well-formed temperature; anything else gives a
+/// meaningless result.
+#[inline]
+pub fn parse(bytes: &[u8]) -> (i32, usize) {
+    parse_word(load(bytes))
+}
+
+/// Parse `value`, which must be exactly one well-formed temperature, into tenths of a degree.
+pub fn parse_exact(value: &[u8]) -> Option<i32> {
+    if !(3..=5).contains(&value.len()) {
+        return None;
+    }
+    let word = load(value);
+    let (tenths, len) = parse_word(word);
+    if len != value.len() {
+        return None;
+    }
+
+    let dot = len - 2;
+    let negative = value[0] == b'-';
+    if value[dot] != b'.' || !(1..=2).contains(&(dot - negative as usize)) {
+        return None;
+    }
+    // every byte but the sign and the `.` must be a digit, i.e. at most 9 above `0`
+    let mut lanes = (1u64 << (8 * len)) - 1;
+    lanes &= !(0xff << (8 * dot));
+    if negative {
+        lanes &= !0xff;
+    }
+    let offsets = (word ^ ZEROS) & lanes;
+    let too_big = (offsets.wrapping_add(0x7676_7676_7676_7676 & lanes) | offsets)
+        & 0x8080_8080_8080_8080
+        & lanes;
+    (too_big == 0).then_some(tenths)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn every_legal_value() {
+        let formatted = (-999..=999)
+            .map(|tenths: i32| {
+                let sign = if tenths < 0 { "-" } else { "" };
+                let text = format!("{sign}{}.{}", tenths.abs() / 10, tenths.abs() % 10);
+                (tenths, text)
+            })
+            .chain([(0, "-0.0".to_owned())]);
+
+        for (tenths, text) in formatted {
+            let value = text.as_bytes();
+            assert_eq!(parse(value), (tenths, value.len()), "{text}");
+            assert_eq!(parse_exact(value), Some(tenths), "{text}");
+
+            let line = format!("{text}\nHamburg;12.0\n");
+            assert_eq!(parse(line.as_bytes()), (tenths, value.len()), "{text}");
+        }
+    }
+
+    #[test]
+    fn malformed_values() {
+        for text in [
+            "", "1", "1.", ".5", "-.5", "1.23", "12,3", "+1.0", "100.0", "-100.0", "1-2.3", "a.b",
+            "1.a", "--1.0", "12.3\r", "NaN", "-",
+        ] {
+            assert_eq!(parse_exact(text.as_bytes()), None, "{text:?}");
+        }
+    }
+}
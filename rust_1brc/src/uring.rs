@@ -0,0 +1,170 @@
+//! An io_uring backed reader for the chunked std solver.
+//!
+//! The pread path blocks on every chunk, so each thread alternates between waiting on the disk and
+//! parsing. Here each worker keeps up to [`QUEUE_DEPTH`] chunk reads in flight and parses whichever
+//! one completes first, so the kernel is already filling the other buffers in the meantime.
+
+use std::{
+    fs::File,
+    io,
+    os::{fd::AsRawFd, unix::fs::FileExt},
+};
+
+use io_uring::{opcode, types, IoUring};
+
+use crate::{
+    aggregate_chunk, align_chunk, chunk_window, schedule::Scheduler, Result, WorkerMap,
+    CHUNK_EXCESS, CHUNK_SIZE,
+};
+
+/// How many chunk reads each worker keeps in flight at once
+const QUEUE_DEPTH: usize = 4;
+
+/// Check whether we are allowed to set up a ring at all.
+///
+/// io_uring may be missing on older kernels, or disabled by seccomp or `kernel.io_uring_disabled`.
+pub fn is_available() -> bool {
+    IoUring::new(1).is_ok()
+}
+
+/// A reusable read buffer, plus the chunk it is currently being filled with.
+struct Slot {
+    buffer: Vec<u8>,
+    /// Where the chunk starts in `buffer`
+    head: usize,
+    read_from: u64,
+    len: usize,
+    /// Whether the chunk is the last of the file
+    ends_file: bool,
+}
+
+/// The ring, and the buffers its reads land in.
+///
+/// Dropping it waits for every read still in flight, so that however the worker loop ends (an
+/// error or a panic included) no buffer is freed while the kernel may still be writing to it.
+struct Reads {
+    ring: IoUring,
+    slots: Vec<Slot>,
+    /// Reads queued or submitted whose completions haven't been reaped yet
+    in_flight: usize,
+}
+
+impl Drop for Reads {
+    fn drop(&mut self) {
+        while self.in_flight > 0 {
+            match self.ring.submit_and_wait(1) {
+                Ok(_) => self.in_flight -= self.ring.completion().count(),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => {
+                    // with no way to tell when the kernel is done with them, the buffers must
+                    // never be freed
+                    std::mem::forget(std::mem::take(&mut self.slots));
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Claim the next chunk and queue a read of it into `slot`.
+///
+/// Returns `false` once every chunk of the file has been claimed.
+fn submit_next(
+    ring: &mut IoUring,
+    fd: types::Fd,
+    scheduler: &Scheduler,
+    worker: usize,
+    idx: usize,
+    slot: &mut Slot,
+) -> Result<bool> {
+    let Some(chunk) = scheduler.next(worker) else {
+        return Ok(false);
+    };
+    let (read_from, len) = chunk_window(&chunk);
+
+    slot.head = (chunk.start - read_from) as usize;
+    slot.read_from = read_from;
+    slot.len = len;
+    slot.ends_file = scheduler.ends_file(&chunk);
+
+    let entry = opcode::Read::new(fd, slot.buffer.as_mut_ptr(), len as u32)
+        .offset(read_from)
+        .build()
+        .user_data(idx as u64);
+
+    // SAFETY: the slot's buffer is neither touched nor dropped until its completion is reaped:
+    // the caller counts the read as in flight, and `Reads` waits for it before freeing the slots
+    unsafe { ring.submission().push(&entry)? };
+
+    Ok(true)
+}
+
+/// Worker loop: process chunks until the scheduler has handed out the whole file.
+pub fn process_chunks(
+    file: &File,
+    scheduler: &Scheduler,
+    worker: usize,
+    map: &mut WorkerMap,
+) -> Result<()> {
+    let mut reads = Reads {
+        ring: IoUring::new(QUEUE_DEPTH as u32)?,
+        slots: (0..QUEUE_DEPTH)
+            .map(|_| Slot {
+                buffer: vec![0; (CHUNK_SIZE + CHUNK_EXCESS) as usize],
+                head: 0,
+                read_from: 0,
+                len: 0,
+                ends_file: false,
+            })
+            .collect(),
+        in_flight: 0,
+    };
+    let Reads {
+        ring,
+        slots,
+        in_flight,
+    } = &mut reads;
+    let fd = types::Fd(file.as_raw_fd());
+
+    for (idx, slot) in slots.iter_mut().enumerate() {
+        if !submit_next(ring, fd, scheduler, worker, idx, slot)? {
+            break;
+        }
+        *in_flight += 1;
+    }
+
+    while *in_flight > 0 {
+        ring.submit_and_wait(1)?;
+
+        loop {
+            let Some(cqe) = ring.completion().next() else {
+                break;
+            };
+            *in_flight -= 1;
+
+            let idx = cqe.user_data() as usize;
+            let slot = &mut slots[idx];
+            if cqe.result() < 0 {
+                return Err(io::Error::from_raw_os_error(-cqe.result()).into());
+            }
+
+            // regular files rarely return short reads, but nothing guarantees it
+            let read = cqe.result() as usize;
+            if read < slot.len {
+                file.read_exact_at(
+                    &mut slot.buffer[read..slot.len],
+                    slot.read_from + read as u64,
+                )?;
+            }
+
+            let aligned = align_chunk(slot.head, &slot.buffer[..slot.len], slot.ends_file);
+            aggregate_chunk(aligned, map)?;
+
+            if submit_next(ring, fd, scheduler, worker, idx, slot)? {
+                *in_flight += 1;
+            }
+        }
+    }
+
+    Ok(())
+}